@@ -0,0 +1,172 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::parser::{OrderRec, Rec};
+
+// 資料品質檢查: 找出解析後的 log 中不合理的記錄，依類別列出其行號及 key
+
+/// 異常的類別
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AnomalyKind {
+	DuplicateRid,      // 多筆 Req 有相同的 SorRID
+	OverwrittenReq,    // Req 的 key 重複，前一筆被覆蓋
+	TimeBackwards,     // 時間比前一筆記錄早
+	MissingFields,     // 欄位數少於表格定義
+	UnknownTable,      // 沒有表格定義的表名
+	BadTimestamp,      // field 3 不是時間
+}
+
+impl AnomalyKind {
+	pub const ALL: [AnomalyKind; 6] = [
+		AnomalyKind::DuplicateRid,
+		AnomalyKind::OverwrittenReq,
+		AnomalyKind::TimeBackwards,
+		AnomalyKind::MissingFields,
+		AnomalyKind::UnknownTable,
+		AnomalyKind::BadTimestamp,
+	];
+}
+
+impl fmt::Display for AnomalyKind {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let text = match self {
+			AnomalyKind::DuplicateRid   => "duplicate SorRID",
+			AnomalyKind::OverwrittenReq => "overwritten Req key",
+			AnomalyKind::TimeBackwards  => "timestamp going backwards",
+			AnomalyKind::MissingFields  => "missing fields",
+			AnomalyKind::UnknownTable   => "unknown table",
+			AnomalyKind::BadTimestamp   => "bad timestamp",
+		};
+		write!(f, "{}", text)
+	}
+}
+
+/// 一筆異常的記錄
+pub struct Anomaly {
+	pub kind   : AnomalyKind,
+	pub line_no: usize,  // 記錄在 log 檔中的行號
+	pub key    : String,
+	pub table  : String,
+	pub detail : String,
+}
+
+impl Anomaly {
+	fn new(kind: AnomalyKind, rec: &Rec, detail: String) -> Anomaly {
+		Anomaly {
+			kind,
+			line_no: rec.get_line_no(),
+			key    : rec.get_key().to_string(),
+			table  : rec.get_table().to_string(),
+			detail,
+		}
+	}
+}
+
+/// 所有記錄 (含被覆蓋的 Req)，依行號排序
+fn all_recs(ord_rec: &OrderRec) -> Vec<&Arc<Rec>> {
+	let mut recs: Vec<&Arc<Rec>> = ord_rec.reqs.values()
+		.chain(ord_rec.ords.values().flatten())
+		.chain(ord_rec.overwritten.iter())
+		.collect();
+	recs.sort_by_key(|rec| rec.get_line_no());
+	recs
+}
+
+/// 檢查所有記錄，依類別、行號排序
+pub fn check(ord_rec: &OrderRec) -> Vec<Anomaly> {
+	let mut anomalies = Vec::new();
+	let mut prev: Option<(f64, usize)> = None; // 前一筆可解析時間的記錄: (時間, 行號)
+	for rec in all_recs(ord_rec) {
+		match ord_rec.tables.get(rec.get_table()) {
+			Some(table) if rec.field_count() < table.recs.len() => {
+				anomalies.push(Anomaly::new(AnomalyKind::MissingFields, rec,
+					format!("{} fields, table has {}", rec.field_count(), table.recs.len())));
+			},
+			Some(_) => {},
+			None => anomalies.push(Anomaly::new(AnomalyKind::UnknownTable, rec, String::new())),
+		}
+		match rec.get_secs() {
+			Some(secs) => {
+				if let Some((prev_secs, prev_line)) = prev {
+					if secs < prev_secs {
+						anomalies.push(Anomaly::new(AnomalyKind::TimeBackwards, rec,
+							format!("{:.6}s before line {}", prev_secs - secs, prev_line)));
+					}
+				}
+				prev = Some((secs, rec.get_line_no()));
+			},
+			None => {
+				anomalies.push(Anomaly::new(AnomalyKind::BadTimestamp, rec, format!("'{}'", rec.get_time_field())));
+			},
+		}
+	}
+
+	for old in &ord_rec.overwritten {
+		let line = ord_rec.reqs.get(old.get_key()).map(|rec| rec.get_line_no()).unwrap_or(0);
+		anomalies.push(Anomaly::new(AnomalyKind::OverwrittenReq, old, format!("replaced by a later Req, last one at line {}", line)));
+	}
+
+	// 相同 SorRID 的 Req (含被覆蓋的)，第一筆之後的每一筆都列出
+	let mut rids = HashMap::<String, Vec<&Arc<Rec>>>::new();
+	for req in ord_rec.reqs.values().chain(ord_rec.overwritten.iter()) {
+		let rid = ord_rec.get_value(req, "SorRID");
+		if !rid.is_empty() {
+			rids.entry(rid).or_default().push(req);
+		}
+	}
+	for (rid, mut reqs) in rids.into_iter().filter(|(_, reqs)| reqs.len() > 1) {
+		reqs.sort_by_key(|rec| rec.get_line_no());
+		for req in &reqs[1..] {
+			anomalies.push(Anomaly::new(AnomalyKind::DuplicateRid, req,
+				format!("SorRID {} first used at line {}", rid, reqs[0].get_line_no())));
+		}
+	}
+
+	anomalies.sort_by(|a, b| a.kind.cmp(&b.kind).then(a.line_no.cmp(&b.line_no)));
+	anomalies
+}
+
+/// 檢查結果的文字報表: 每個類別的筆數，及有異常的記錄
+pub fn format_report(ord_rec: &OrderRec) -> String {
+	let anomalies = check(ord_rec);
+	let mut ret = format!("{} anomalies\n", anomalies.len());
+	for kind in AnomalyKind::ALL {
+		let found: Vec<&Anomaly> = anomalies.iter().filter(|a| a.kind == kind).collect();
+		ret.push_str(&format!("{}: {}\n", kind, found.len()));
+		for anomaly in found {
+			ret.push_str(&format!("\tline {}\t{}\t{}\t{}\n", anomaly.line_no, anomaly.table, anomaly.key, anomaly.detail));
+		}
+	}
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parser::tests::parse;
+
+	#[test]
+	fn check_finds_each_kind() {
+		let parser = parse(&[
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Req|R2|TwfNew|1704157199.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Req|R1|TwfNew|1704157201.000001|1|SorAPI|9A95|123|uid1|u1|100003|2330|B|600|1000",
+			"Ord|O1|TwfOrd|1704157202.000001|R1",
+			"Req|R3|TwfXyz|1704157203.000001|1|SorAPI|9A95|123|uid1|u1|100004|2330|B|600|1000",
+			"Req|R4|TwfNew|bad|1|SorAPI|9A95|123|uid1|u1|100005|2330|B|600|1000",
+		]);
+		let anomalies = check(&parser.ord_rec);
+		let found: Vec<(AnomalyKind, usize)> = anomalies.iter().map(|a| (a.kind, a.line_no)).collect();
+		assert!(found == [
+			(AnomalyKind::DuplicateRid, 6),
+			(AnomalyKind::OverwrittenReq, 5),
+			(AnomalyKind::TimeBackwards, 6),
+			(AnomalyKind::MissingFields, 8),
+			(AnomalyKind::UnknownTable, 9),
+			(AnomalyKind::BadTimestamp, 10),
+		]);
+		assert_eq!(anomalies[1].key, "R1");
+		assert_eq!(anomalies[1].detail, "replaced by a later Req, last one at line 7");
+	}
+}
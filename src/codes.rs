@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Error, ErrorKind, Result};
+use std::sync::OnceLock;
+
+use serde::Deserialize;
+
+// 代碼對照表: ReqKind、OrderSt、ReqStep 等代碼的中英文說明及 PKI 字元
+// 內建的對照表即為 codes.toml，可用 --codes 載入修改過的檔案
+
+/// 內建的代碼對照表
+const DEFAULT_CODES: &str = include_str!("../codes.toml");
+
+/// 目前使用的對照表，main 開始時以 install 設定，未設定時使用內建的
+static CODES: OnceLock<CodeBook> = OnceLock::new();
+
+/// 一個代碼的說明
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct CodeLabel {
+	pub zh : String,
+	pub en : String,
+	pub pki: String, // PKI 記錄 @kind 的字元 (只用於 ReqKind)
+}
+
+impl CodeLabel {
+	/// 指定語言的說明，沒有時取另一個語言的
+	pub fn text(&self, lang: &str) -> &str {
+		let (first, second) = if lang == "en" { (&self.en, &self.zh) } else { (&self.zh, &self.en) };
+		if first.is_empty() { second } else { first }
+	}
+}
+
+/// 改量要求的委託量欄位的意義
+#[derive(Deserialize, Clone, Copy, PartialEq, Default, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ChgQtyMode {
+	#[default]
+	Reduce, // 減少的量，負數為增加
+	Total,  // 改量後的委託量
+}
+
+/// 成交統計 (--fills、--positions) 使用的欄位名及要求種類
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct FillCodes {
+	pub qty_field     : String,      // 新單、改量要求的委託量
+	pub deal_qty_field: String,      // 成交回報的成交量
+	pub deal_pri_field: String,      // 成交回報的成交價
+	pub new_kinds     : Vec<String>, // 新單的 ReqKind
+	pub chg_qty_kinds : Vec<String>, // 改量的 ReqKind
+	pub deal_kinds    : Vec<String>, // 成交回報的 ReqKind
+	pub chg_qty       : ChgQtyMode,
+	pub symbol_field  : String,      // 新單的商品代號
+	pub side_field    : String,      // 新單的買賣別
+	pub buy_side      : String,      // 買賣別的買進值
+	pub sell_side     : String,      // 買賣別的賣出值
+}
+
+impl Default for FillCodes {
+	fn default() -> FillCodes {
+		let kinds = |codes: &[&str]| codes.iter().map(|code| code.to_string()).collect();
+		FillCodes {
+			qty_field     : "Qty".to_string(),
+			deal_qty_field: "DealQty".to_string(),
+			deal_pri_field: "DealPri".to_string(),
+			new_kinds     : kinds(&["1"]),
+			chg_qty_kinds : kinds(&["2"]),
+			deal_kinds    : kinds(&["10", "11"]),
+			chg_qty       : ChgQtyMode::Reduce,
+			symbol_field  : "Symbol".to_string(),
+			side_field    : "Side".to_string(),
+			buy_side      : "B".to_string(),
+			sell_side     : "S".to_string(),
+		}
+	}
+}
+
+/// 欄位名 - 代碼 - 說明
+type FieldCodes = HashMap<String, HashMap<String, CodeLabel>>;
+
+#[derive(Deserialize, Clone, Default, Debug)]
+#[serde(default)]
+pub struct CodeBook {
+	pub lang   : String,
+	pub unknown: CodeLabel,
+	pub codes  : FieldCodes,
+	pub tables : HashMap<String, FieldCodes>, // 表名 - 該表的代碼，優先於 codes
+	pub fills  : FillCodes,
+}
+
+impl CodeBook {
+	/// 內建的對照表
+	pub fn builtin() -> CodeBook {
+		// 內建的對照表必定合法
+		toml::from_str(DEFAULT_CODES).expect("built-in codes.toml")
+	}
+	/// 從 TOML 檔載入對照表，沒有 [unknown] 時使用內建的說明
+	pub fn load(path: &str) -> Result<CodeBook> {
+		let content = fs::read_to_string(path)?;
+		let mut book: CodeBook = toml::from_str(&content)
+			.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+		if book.unknown.zh.is_empty() && book.unknown.en.is_empty() {
+			book.unknown = CodeBook::builtin().unknown;
+		}
+		Ok(book)
+	}
+	/// 表格 table 的欄位 field 中代碼 code 的說明，先找該表格的，再找共用的
+	pub fn get(&self, table: &str, field: &str, code: &str) -> Option<&CodeLabel> {
+		self.tables.get(table).and_then(|fields| fields.get(field)).and_then(|codes| codes.get(code))
+			.or_else(|| self.codes.get(field).and_then(|codes| codes.get(code)))
+	}
+	/// 代碼的說明，沒有對照時為 未知(代碼)
+	pub fn label(&self, table: &str, field: &str, code: &str) -> String {
+		match self.get(table, field, code) {
+			Some(label) => label.text(&self.lang).to_string(),
+			None if code.is_empty() => self.unknown.text(&self.lang).to_string(),
+			None => format!("{}({})", self.unknown.text(&self.lang), code),
+		}
+	}
+	/// 共用的 ReqKind 對應的 PKI 字元 (PkiLayout 的預設 kinds)
+	pub fn pki_kinds(&self) -> HashMap<String, String> {
+		pki_chars(&self.codes)
+	}
+	/// 個別表格的 ReqKind 對應的 PKI 字元 (PkiLayout 的預設 table_kinds)，只列有 pki 的表格
+	pub fn pki_table_kinds(&self) -> HashMap<String, HashMap<String, String>> {
+		self.tables.iter()
+			.map(|(table, fields)| (table.clone(), pki_chars(fields)))
+			.filter(|(_, kinds)| !kinds.is_empty())
+			.collect()
+	}
+}
+
+/// ReqKind - 有 pki 的代碼的 PKI 字元
+fn pki_chars(fields: &FieldCodes) -> HashMap<String, String> {
+	fields.get("ReqKind")
+		.map(|codes| codes.iter()
+			.filter(|(_, label)| !label.pki.is_empty())
+			.map(|(code, label)| (code.clone(), label.pki.clone()))
+			.collect())
+		.unwrap_or_default()
+}
+
+/// 設定使用的對照表，lang 不為空時取代檔案中的語言
+/// 只能在第一次取用對照表前設定
+pub fn install(mut book: CodeBook, lang: &str) {
+	if !lang.is_empty() {
+		book.lang = lang.to_string();
+	}
+	if CODES.set(book).is_err() {
+		println!("code table already in use, --codes/--lang ignored");
+	}
+}
+
+/// 目前使用的對照表
+pub fn book() -> &'static CodeBook {
+	CODES.get_or_init(CodeBook::builtin)
+}
+
+/// 代碼的說明，見 CodeBook::label
+pub fn label(table: &str, field: &str, code: &str) -> String {
+	book().label(table, field, code)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	const CODES: &str = r#"
+lang = "en"
+[unknown]
+en = "unknown"
+[codes.ReqKind]
+1 = { zh = "新單", en = "New", pki = "O" }
+4 = { zh = "刪單", pki = "C" }
+10 = { zh = "成交", en = "Deal" }
+[tables.FrfNew.ReqKind]
+1 = { zh = "外期新單", en = "Foreign new", pki = "F" }
+[tables.FrfNew.OrderSt]
+99 = { en = "Foreign rejected" }
+"#;
+
+	#[test]
+	fn table_codes_come_first() {
+		let book: CodeBook = toml::from_str(CODES).unwrap();
+		assert_eq!(book.label("FrfNew", "ReqKind", "1"), "Foreign new");
+		assert_eq!(book.label("TwfNew", "ReqKind", "1"), "New");
+		assert_eq!(book.label("FrfNew", "ReqKind", "4"), "刪單");
+		assert_eq!(book.label("TwfNew", "OrderSt", "99"), "unknown(99)");
+		assert_eq!(book.label("TwfNew", "OrderSt", ""), "unknown");
+	}
+
+	#[test]
+	fn pki_kinds_by_table() {
+		let book: CodeBook = toml::from_str(CODES).unwrap();
+		let kinds = book.pki_kinds();
+		assert_eq!(kinds.len(), 2);
+		assert_eq!(kinds["1"], "O");
+		assert_eq!(kinds["4"], "C");
+		let table_kinds = book.pki_table_kinds();
+		assert_eq!(table_kinds.len(), 1);
+		assert_eq!(table_kinds["FrfNew"]["1"], "F");
+	}
+
+	#[test]
+	fn builtin_codes_parse() {
+		let book = CodeBook::builtin();
+		assert_eq!(book.pki_kinds()["1"], "O");
+		assert!(!book.unknown.zh.is_empty());
+	}
+}
@@ -1,9 +1,12 @@
 use std::io::*;
 use std::io::{BufRead, BufReader};
+use std::fs::File;
+use std::path::Path;
 use encoding::{Encoding, DecoderTrap};
 use encoding::all::{ BIG5_2003, GB18030, ISO_2022_JP };
 
 use crate::parser::Parser;
+use crate::snapshot;
 //use crate::rpt_parser::RptParser;
 
 pub enum LineType<T> {
@@ -24,7 +27,7 @@ enum EncodingType {
 
 /// get line from reader
 #[allow(dead_code)]
-fn get_reader_line<R: Read>(reader: &mut BufReader<R>, encoding: &EncodingType) -> LineType<String> {
+fn get_reader_line<R: Read>(reader: &mut BufReader<R>, encoding: &EncodingType, bytes_read: &mut u64) -> LineType<String> {
 	let mut line_buf = Vec::<u8>::new();
 	let mut line = String::new();
 	// 讀第一行
@@ -34,16 +37,15 @@ fn get_reader_line<R: Read>(reader: &mut BufReader<R>, encoding: &EncodingType)
 			if sz_line == 0 {
 				return LineType::EndOfFile;
 			}
-			let mut dont_need_utf8 = true;
-			if encoding != &EncodingType::UTF8 {
-				dont_need_utf8 = match encoding {
-					EncodingType::BIG5 => BIG5_2003.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
-					EncodingType::JP => ISO_2022_JP.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
-					EncodingType::GB => GB18030.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
-					_ => false,
-				};
-			}
-			if !dont_need_utf8 {
+			*bytes_read += sz_line as u64;
+			// UTF8 或解碼失敗時，直接以 UTF8 轉換
+			let decoded = match encoding {
+				EncodingType::BIG5 => BIG5_2003.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
+				EncodingType::JP => ISO_2022_JP.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
+				EncodingType::GB => GB18030.decode_to(&line_buf, DecoderTrap::Strict, &mut line).is_ok(),
+				EncodingType::UTF8 => false,
+			};
+			if !decoded {
 				line = String::from_utf8_lossy(&line_buf).to_string();
 			}
 			line = line.trim().to_string();
@@ -72,23 +74,105 @@ fn get_encoding_constant(encoding_opt: &str) -> EncodingType {
 	}
 }
 
+/// 偵測編碼時讀取的檔頭大小
+const DETECT_BYTES: u64 = 256 * 1024;
+
+/// 由檔頭猜測檔案的編碼，回傳 BIG5/GB/JP/UTF8；全為 ASCII 時使用預設的 BIG5
+pub fn detect_encoding(path: &Path) -> &'static str {
+	let mut buf = Vec::<u8>::new();
+	if let Ok(f) = File::open(path) {
+		let _ = f.take(DETECT_BYTES).read_to_end(&mut buf);
+	}
+	// 避開被截斷的最後一行
+	if buf.len() as u64 == DETECT_BYTES {
+		if let Some(pos) = buf.iter().rposition(|b| *b == b'\n') {
+			buf.truncate(pos + 1);
+		}
+	}
+	if buf.windows(3).any(|w| w == b"\x1b$B" || w == b"\x1b$@") {
+		"JP"
+	} else if buf.is_ascii() {
+		"BIG5"
+	} else if std::str::from_utf8(&buf).is_ok() {
+		"UTF8"
+	} else if BIG5_2003.decode(&buf, DecoderTrap::Strict).is_ok() {
+		"BIG5"
+	} else if GB18030.decode(&buf, DecoderTrap::Strict).is_ok() {
+		"GB"
+	} else {
+		"BIG5"
+	}
+}
+
+/// 將 log 行接到暫存的 log 之後，行與行之間以 '\n' 分隔
+fn push_log_line(log_tmp: &mut String, log: &str) {
+	if !log_tmp.is_empty() {
+		log_tmp.push('\n');
+	}
+	log_tmp.push_str(log);
+}
+
+/// 每解析多少筆記錄回報一次進度
+const PROGRESS_INTERVAL: usize = 5000;
+
 /// line by line with log 解析
+#[allow(dead_code)]
 pub fn read_data_log<R: Read>(reader: &mut BufReader<R>, parser: &mut Parser, encoding_opt: &str) {
+	read_data_log_with_progress(reader, parser, encoding_opt, &mut |_, _| true);
+}
+
+/// 讀入 log 檔: 有仍有效的快照時直接載入，否則解析後存成快照 (見 snapshot)
+pub fn read_log_file(path: &Path, parser: &mut Parser, encoding_opt: &str) -> Result<()> {
+	read_log_file_with_progress(path, parser, encoding_opt, &mut |_, _| true).map(|_| ())
+}
+
+/// 同 read_log_file，解析時定期呼叫 progress (見 read_data_log_with_progress)
+/// 回傳是否讀完，中途取消時不存快照
+pub fn read_log_file_with_progress(path: &Path, parser: &mut Parser, encoding_opt: &str, progress: &mut dyn FnMut(u64, usize) -> bool) -> Result<bool> {
+	if let Some(ord_rec) = snapshot::load(path, encoding_opt) {
+		println!("loaded snapshot of {}", path.display());
+		parser.ord_rec = ord_rec;
+		let size = std::fs::metadata(path).map(|m| m.len()).unwrap_or(0);
+		let rec_count = parser.ord_rec.reqs.len() + parser.ord_rec.ords.values().map(Vec::len).sum::<usize>();
+		return Ok(progress(size, rec_count));
+	}
+	let mut reader = BufReader::new(File::open(path)?);
+	let completed = read_data_log_with_progress(&mut reader, parser, encoding_opt, progress);
+	if completed {
+		snapshot::save(path, encoding_opt, &parser.ord_rec);
+	}
+	Ok(completed)
+}
+
+/// 同 read_data_log，並定期以 (已讀取位元組數, 已解析記錄數) 呼叫 progress
+/// progress 回傳 false 時停止解析，此時回傳 false
+pub fn read_data_log_with_progress<R: Read>(reader: &mut BufReader<R>, parser: &mut Parser, encoding_opt: &str, progress: &mut dyn FnMut(u64, usize) -> bool) -> bool {
 	println!("parsing data...");
+	let mut bytes_read: u64 = 0;
+	let mut rec_count: usize = 0;
+	let mut line_no: usize = 0;
+	let mut rec_line: usize = 0;
 	let mut rec_tmp: String = "".to_string();
 	let mut log_tmp: String = "".to_string();
 	let mut digsgn_tmp: String = "".to_string();
 	let encoding = get_encoding_constant(encoding_opt);
 	loop {
-		match get_reader_line(reader, &encoding) {
+		let line_type = get_reader_line(reader, &encoding, &mut bytes_read);
+		line_no += 1;
+		match line_type {
 			// 先把讀到的記錄暫存起來，為要和log一起parse
 			LineType::Rec(line) => {
 				if !rec_tmp.is_empty() {
-					parser.parse_line(&rec_tmp, &log_tmp, &digsgn_tmp);
+					parser.parse_line(&rec_tmp, &log_tmp, &digsgn_tmp, rec_line);
 					log_tmp.clear();
 					digsgn_tmp.clear();
+					rec_count += 1;
+					if rec_count.is_multiple_of(PROGRESS_INTERVAL) && !progress(bytes_read, rec_count) {
+						return false;
+					}
 				}
 				rec_tmp = line;
+				rec_line = line_no;
 			},
 			// log 和 ext log 串成一串，等待rec再一併被parse
 			LineType::Log(log)    => {
@@ -99,14 +183,18 @@ pub fn read_data_log<R: Read>(reader: &mut BufReader<R>, parser: &mut Parser, en
 						digsgn_tmp = parts[parts.len() - 1].to_string();
 					}
 				}
-				log_tmp = log_tmp + &log;
+				push_log_line(&mut log_tmp, &log);
 			},
-			LineType::LogExt(log) => log_tmp = log_tmp + &log,
+			LineType::LogExt(log) => push_log_line(&mut log_tmp, &log),
 			LineType::Empty     =>  continue,
 			LineType::EndOfFile =>  break,
 		};
 	};
-	parser.parse_line(&rec_tmp, &log_tmp, &digsgn_tmp);
+	parser.parse_line(&rec_tmp, &log_tmp, &digsgn_tmp, rec_line);
+	if !rec_tmp.is_empty() {
+		rec_count += 1;
+	}
+	progress(bytes_read, rec_count)
 }
 
 //回報LOG檔解析
@@ -0,0 +1,255 @@
+use std::collections::{HashSet, LinkedList};
+use std::fmt;
+use std::sync::Arc;
+
+use crate::codes::{self, ChgQtyMode, FillCodes};
+use crate::parser::{OrderRec, Rec};
+
+// 成交統計: 由成交回報 (預設為 ReqKind 10/11 的 Req) 累計每筆委託的成交量及均價，
+// 與委託量及最後的委託狀態 (110 部份成交 / 111 全部成交) 比對，找出不合理的委託
+// 欄位名及要求種類見代碼對照表的 [fills]
+
+/// 委託狀態: 部份成交、全部成交、交易所取消
+const ST_PARTIAL: i32 = 110;
+const ST_FULL   : i32 = 111;
+const ST_CANCEL : i32 = 120;
+
+/// 成交與委託不符的情形
+#[derive(Clone, Copy, PartialEq)]
+pub enum FillProblem {
+	Overfill,         // 成交量大於委託量
+	FillAfterCancel,  // 委託取消後仍有成交
+	FullMismatch,     // 狀態為全部成交，但成交量不等於委託量
+	PartialMismatch,  // 狀態為部份成交，但沒有成交或已全部成交
+	NoNewOrder,       // 沒有新單，無法得知委託量
+}
+
+impl fmt::Display for FillProblem {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let text = match self {
+			FillProblem::Overfill        => "overfill",
+			FillProblem::FillAfterCancel => "fill after cancel",
+			FillProblem::FullMismatch    => "full fill mismatch",
+			FillProblem::PartialMismatch => "partial fill mismatch",
+			FillProblem::NoNewOrder      => "no new-order request",
+		};
+		write!(f, "{}", text)
+	}
+}
+
+/// 一筆委託的成交統計
+pub struct OrderFill {
+	pub ord_key  : String,
+	pub rid      : String,
+	pub ordno    : String,
+	pub table    : String,
+	pub order_qty: i64,
+	pub filled   : i64,
+	pub deals    : usize,
+	pub avg_price: f64,
+	pub order_st : i32,   // 最後一筆 Ord 的 OrderSt
+	pub problems : Vec<FillProblem>,
+}
+
+pub fn parse_num(val: &str) -> f64 {
+	val.trim().parse::<f64>().unwrap_or(0.0)
+}
+
+/// 要求種類是否在 kinds 中
+fn is_kind(ord_rec: &OrderRec, rec: &Rec, kinds: &[String]) -> bool {
+	kinds.contains(&ord_rec.get_value(rec, "ReqKind"))
+}
+
+/// 是否為成交回報
+pub fn is_deal(ord_rec: &OrderRec, rec: &Rec) -> bool {
+	rec.is_req() && is_kind(ord_rec, rec, &codes::book().fills.deal_kinds)
+}
+
+/// 成交回報的 (成交量, 成交價)
+pub fn deal_fill(ord_rec: &OrderRec, deal: &Rec) -> (f64, f64) {
+	let fields = &codes::book().fills;
+	(parse_num(&ord_rec.get_value(deal, &fields.deal_qty_field)), parse_num(&ord_rec.get_value(deal, &fields.deal_pri_field)))
+}
+
+/// 委託的 Req (同一筆只列一次) 及 Ord
+pub fn split_order(list: &LinkedList<Arc<Rec>>) -> (Vec<&Arc<Rec>>, Vec<&Arc<Rec>>) {
+	let mut seen = HashSet::<&str>::new();
+	let reqs = list.iter().filter(|rec| rec.is_req() && seen.insert(rec.get_key())).collect();
+	let ords = list.iter().filter(|rec| !rec.is_req()).collect();
+	(reqs, ords)
+}
+
+/// 委託的新單 Req
+pub fn new_order_req<'a>(ord_rec: &OrderRec, reqs: &[&'a Arc<Rec>]) -> Option<&'a Arc<Rec>> {
+	reqs.iter().find(|rec| is_kind(ord_rec, rec, &codes::book().fills.new_kinds)).copied()
+}
+
+/// 委託量: 新單的量，依序套用改量要求
+/// 改量的量依 chg_qty 為減少的量 (負數為增加) 或改量後的委託量
+fn order_qty(ord_rec: &OrderRec, fields: &FillCodes, new_req: Option<&Arc<Rec>>, reqs: &[&Arc<Rec>]) -> i64 {
+	let qty = |rec: &Rec| parse_num(&ord_rec.get_value(rec, &fields.qty_field)) as i64;
+	let mut order_qty = new_req.map(|rec| qty(rec)).unwrap_or(0);
+	for req in reqs.iter().filter(|rec| is_kind(ord_rec, rec, &fields.chg_qty_kinds)) {
+		match fields.chg_qty {
+			ChgQtyMode::Reduce => order_qty -= qty(req),
+			ChgQtyMode::Total  => order_qty = qty(req),
+		}
+	}
+	order_qty
+}
+
+/// 統計一筆委託，沒有成交也不是成交狀態的委託回傳 None
+fn order_fill(ord_rec: &OrderRec, ord_key: &str) -> Option<OrderFill> {
+	let list = ord_rec.get_target_ordlist(ord_key);
+	let (reqs, ords) = split_order(&list);
+	let order_st = ords.last().and_then(|rec| ord_rec.get_value(rec, "OrderSt").parse::<i32>().ok()).unwrap_or(0);
+	let deals: Vec<&Arc<Rec>> = reqs.iter().filter(|rec| is_deal(ord_rec, rec)).copied().collect();
+	if deals.is_empty() && order_st != ST_PARTIAL && order_st != ST_FULL {
+		return None;
+	}
+
+	let new_req = new_order_req(ord_rec, &reqs);
+	let order_qty = order_qty(ord_rec, &codes::book().fills, new_req, &reqs);
+
+	let mut filled: i64 = 0;
+	let mut amount = 0.0;
+	for deal in &deals {
+		let (qty, price) = deal_fill(ord_rec, deal);
+		filled += qty as i64;
+		amount += qty * price;
+	}
+	let avg_price = if filled > 0 { amount / filled as f64 } else { 0.0 };
+
+	let mut problems = Vec::new();
+	if new_req.is_none() {
+		problems.push(FillProblem::NoNewOrder);
+	} else if filled > order_qty {
+		problems.push(FillProblem::Overfill);
+	}
+	let cancel_secs = ords.iter()
+		.find(|rec| ord_rec.get_value(rec, "OrderSt").parse::<i32>().ok() == Some(ST_CANCEL))
+		.and_then(|rec| rec.get_secs());
+	if let Some(cancel_secs) = cancel_secs {
+		if deals.iter().any(|deal| deal.get_secs().is_some_and(|secs| secs > cancel_secs)) {
+			problems.push(FillProblem::FillAfterCancel);
+		}
+	}
+	// 沒有新單時委託量不明，不比對成交量
+	if new_req.is_some() && order_st == ST_FULL && filled != order_qty {
+		problems.push(FillProblem::FullMismatch);
+	}
+	if new_req.is_some() && order_st == ST_PARTIAL && (filled == 0 || filled >= order_qty) {
+		problems.push(FillProblem::PartialMismatch);
+	}
+
+	let info = ord_rec.get_ord_summary(&list);
+	Some(OrderFill {
+		ord_key : ord_key.to_string(),
+		rid     : info.rid().to_string(),
+		ordno   : info.ordno().to_string(),
+		table   : new_req.map(|rec| rec.get_table().to_string()).unwrap_or_default(),
+		order_qty,
+		filled,
+		deals   : deals.len(),
+		avg_price,
+		order_st,
+		problems,
+	})
+}
+
+/// 所有有成交或成交狀態的委託，依委託的第一筆記錄時間排序
+pub fn fill_report(ord_rec: &OrderRec) -> Vec<OrderFill> {
+	let mut keys: Vec<(&String, f64)> = ord_rec.ords.iter()
+		.map(|(key, ords)| (key, ords.first().and_then(|rec| rec.get_secs()).unwrap_or(0.0)))
+		.collect();
+	keys.sort_by(|a, b| a.1.total_cmp(&b.1).then_with(|| a.0.cmp(b.0)));
+	keys.into_iter().filter_map(|(key, _)| order_fill(ord_rec, key)).collect()
+}
+
+/// 沒有對應到委託的成交回報數
+pub fn unlinked_deals(ord_rec: &OrderRec) -> usize {
+	ord_rec.reqs.values()
+		.filter(|req| is_deal(ord_rec, req))
+		.filter(|req| ord_rec.get_ord_key(req.get_key()).is_none())
+		.count()
+}
+
+/// 成交統計的文字報表，only_problems 時只列出有問題的委託
+pub fn format_report(ord_rec: &OrderRec, only_problems: bool) -> String {
+	let fills = fill_report(ord_rec);
+	let flagged = fills.iter().filter(|fill| !fill.problems.is_empty()).count();
+	let mut ret = format!("{} orders with fills, {} flagged, {} deals without order\n", fills.len(), flagged, unlinked_deals(ord_rec));
+	ret.push_str("OrdKey\tSorRID\tOrdNo\tTable\tOrderQty\tFilled\tAvgPri\tDeals\tOrderSt\tProblems\n");
+	for fill in fills.iter().filter(|fill| !only_problems || !fill.problems.is_empty()) {
+		let problems: Vec<String> = fill.problems.iter().map(|p| p.to_string()).collect();
+		ret.push_str(&format!("{}\t{}\t{}\t{}\t{}\t{}\t{:.4}\t{}\t{}\t{}\n",
+			fill.ord_key, fill.rid, fill.ordno, fill.table, fill.order_qty, fill.filled,
+			fill.avg_price, fill.deals, fill.order_st, problems.join("; ")));
+	}
+	ret
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parser::Parser;
+	use crate::parser::tests::parse;
+
+	/// 新單 R1 (Qty 10) 及兩筆改量 R2 (Qty 3)、R3 (Qty -5)，成交 R4 (7 @ 100)，最後狀態為全部成交
+	fn parse_order() -> Parser {
+		parse(&[
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|10",
+			"Ord|O1|TwfOrd|1704157201.000001|R1|A01|101|7|10|",
+			"Req|R2|TwfChg|1704157202.000001|2|SorAPI|u1|100002|3",
+			"Ord|O1|TwfOrd|1704157203.000001|R2|A01|101|7|7|",
+			"Req|R3|TwfChg|1704157204.000001|2|SorAPI|u1|100003|-5",
+			"Ord|O1|TwfOrd|1704157205.000001|R3|A01|101|7|12|",
+			"Req|R4|TwfDeal|1704157206.000001|10|u1|7|100",
+			"Ord|O1|TwfOrd|1704157207.000001|R4|A01|111|7|5|",
+		])
+	}
+
+	#[test]
+	fn change_qty_reduces_or_increases() {
+		let parser = parse_order();
+		let ord_rec = &parser.ord_rec;
+		let list = ord_rec.get_target_ordlist("O1");
+		let (reqs, _) = split_order(&list);
+		let new_req = new_order_req(ord_rec, &reqs);
+		assert_eq!(new_req.map(|rec| rec.get_key()), Some("R1"));
+
+		let mut fields = FillCodes::default();
+		assert_eq!(order_qty(ord_rec, &fields, new_req, &reqs), 12);
+		fields.chg_qty = ChgQtyMode::Total;
+		assert_eq!(order_qty(ord_rec, &fields, new_req, &reqs[..2]), 3);
+	}
+
+	#[test]
+	fn fill_report_flags_mismatch() {
+		let parser = parse_order();
+		let fills = fill_report(&parser.ord_rec);
+		assert_eq!(fills.len(), 1);
+		let fill = &fills[0];
+		assert_eq!((fill.order_qty, fill.filled, fill.deals, fill.order_st), (12, 7, 1, 111));
+		assert!((fill.avg_price - 100.0).abs() < 1e-9);
+		assert!(fill.problems == [FillProblem::FullMismatch]);
+		assert_eq!(unlinked_deals(&parser.ord_rec), 0);
+	}
+
+	#[test]
+	fn order_without_new_request_is_not_overfilled() {
+		let parser = parse(&[
+			"Req|R4|TwfDeal|1704157206.000001|10|u1|7|100",
+			"Ord|O1|TwfOrd|1704157207.000001|R4|A01|111|7|5|",
+		]);
+		let ord_rec = &parser.ord_rec;
+		let list = ord_rec.get_target_ordlist("O1");
+		let (reqs, _) = split_order(&list);
+		assert!(new_order_req(ord_rec, &reqs).is_none());
+
+		let fills = fill_report(ord_rec);
+		assert_eq!(fills.len(), 1);
+		assert_eq!((fills[0].order_qty, fills[0].filled), (0, 7));
+		assert!(fills[0].problems == [FillProblem::NoNewOrder]);
+	}
+}
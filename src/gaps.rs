@@ -0,0 +1,253 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use chrono::{Local, NaiveTime, TimeZone, Timelike};
+use serde_json::{json, Value};
+
+use crate::parser::{OrderRec, Rec};
+
+// 記錄中斷檢查: SOR 停住時 log 只會留下一段空白
+// 找出交易時段內相鄰記錄時間相差超過門檻的區間，及中斷後的爆量與當時處理中的委託
+
+/// 預設的交易時段: 期交所日盤及夜盤
+pub const DEFAULT_TRADING_HOURS: &str = "08:45-13:45,15:00-05:00";
+/// 預設的中斷門檻 (秒)
+pub const DEFAULT_GAP_SECS: f64 = 5.0;
+
+/// 中斷後多少秒內的記錄數視為爆量的依據
+const BURST_WINDOW_SECS: f64 = 1.0;
+/// 中斷後的記錄數超過平均每秒記錄數的幾倍時視為爆量
+const BURST_FACTOR: f64 = 2.0;
+
+/// 委託狀態: 小於委託成功 (90) 為傳送中、已傳送
+const ST_ACCEPTED: i32 = 90;
+
+/// 交易時段，每段為 (開始, 結束) 當天的分鐘數，結束早於開始時跨到隔天
+pub struct TradingHours {
+	ranges: Vec<(u32, u32)>,
+}
+
+impl TradingHours {
+	/// 解析 "HH:MM-HH:MM"，多段以 , 連接，例如 08:45-13:45,15:00-05:00
+	pub fn parse(text: &str) -> Result<TradingHours, String> {
+		let minutes = |s: &str| -> Result<u32, String> {
+			NaiveTime::parse_from_str(s.trim(), "%H:%M")
+				.map(|t| t.hour() * 60 + t.minute())
+				.map_err(|_| format!("invalid time '{}', please use HH:MM", s.trim()))
+		};
+		let mut ranges = Vec::new();
+		for range in text.split(',').filter(|r| !r.trim().is_empty()) {
+			match range.split_once('-') {
+				Some((start, end)) => ranges.push((minutes(start)?, minutes(end)?)),
+				None => return Err(format!("invalid trading hours '{}', please use HH:MM-HH:MM", range)),
+			}
+		}
+		if ranges.is_empty() {
+			return Err("no trading hours given".to_string());
+		}
+		Ok(TradingHours { ranges })
+	}
+	/// [from, to] 落在交易時段內的秒數
+	pub fn overlap(&self, from: f64, to: f64) -> f64 {
+		let (Some(first), Some(last)) = (Local.timestamp_opt(from as i64, 0).earliest(), Local.timestamp_opt(to as i64, 0).earliest()) else {
+			return 0.0;
+		};
+		// 前一天開始的跨日時段也可能涵蓋 from
+		let mut day = first.date_naive().pred_opt().unwrap_or(first.date_naive());
+		let mut secs = 0.0;
+		while day <= last.date_naive() {
+			if let Some(midnight) = day.and_hms_opt(0, 0, 0).and_then(|t| Local.from_local_datetime(&t).earliest()) {
+				let base = midnight.timestamp() as f64;
+				for &(start, end) in &self.ranges {
+					let start = base + start as f64 * 60.0;
+					let mut end = base + end as f64 * 60.0;
+					if end <= start {
+						end += 86400.0;
+					}
+					secs += (to.min(end) - from.max(start)).max(0.0);
+				}
+			}
+			day = match day.succ_opt() {
+				Some(next) => next,
+				None => break,
+			};
+		}
+		secs
+	}
+}
+
+/// 中斷時處理中的委託
+pub struct InFlight {
+	pub ord_key: String,
+	pub rid    : String,
+	pub last   : Arc<Rec>, // 中斷前該委託的最後一筆記錄
+}
+
+/// 一段記錄中斷
+pub struct Gap {
+	pub from     : Arc<Rec>, // 中斷前的最後一筆記錄
+	pub to       : Arc<Rec>, // 中斷後的第一筆記錄
+	pub secs     : f64,
+	pub in_hours : f64,      // 落在交易時段內的秒數
+	pub after    : usize,    // 中斷後 BURST_WINDOW_SECS 秒內的記錄數
+	pub burst    : bool,
+	pub in_flight: Vec<InFlight>,
+}
+
+/// 中斷檢查的結果
+pub struct GapReport {
+	pub records : usize,
+	pub avg_rate: f64,  // 有記錄的每秒平均記錄數
+	pub gaps    : Vec<Gap>,
+}
+
+/// 記錄依時間排序，沒有時間的略過
+fn timed_recs(ord_rec: &OrderRec) -> Vec<(f64, &Arc<Rec>)> {
+	let mut recs: Vec<(f64, &Arc<Rec>)> = ord_rec.reqs.values()
+		.chain(ord_rec.ords.values().flatten())
+		.filter_map(|rec| rec.get_secs().map(|secs| (secs, rec)))
+		.collect();
+	recs.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.1.get_line_no().cmp(&b.1.get_line_no())));
+	recs
+}
+
+/// 在 secs 時處理中的委託: 當時最後一筆記錄為 Req (尚未回覆)，或 Ord 的狀態為傳送中、已傳送
+fn in_flight_at(ord_rec: &OrderRec, secs: f64) -> Vec<InFlight> {
+	let mut ret = Vec::new();
+	for ord_key in ord_rec.ords.keys() {
+		let list = ord_rec.get_target_ordlist(ord_key);
+		let last = list.iter()
+			.filter_map(|rec| rec.get_secs().filter(|t| *t <= secs).map(|t| (t, rec)))
+			.max_by(|a, b| a.0.total_cmp(&b.0).then(a.1.get_line_no().cmp(&b.1.get_line_no())))
+			.map(|(_, rec)| rec);
+		let Some(last) = last else { continue };
+		let pending = last.is_req()
+			|| ord_rec.get_value(last, "OrderSt").parse::<i32>().is_ok_and(|st| st < ST_ACCEPTED);
+		if pending {
+			ret.push(InFlight {
+				ord_key: ord_key.clone(),
+				rid    : ord_rec.get_ord_summary(&list).rid().to_string(),
+				last   : Arc::clone(last),
+			});
+		}
+	}
+	ret.sort_by_key(|order| order.last.get_line_no());
+	ret
+}
+
+/// 找出交易時段內超過 min_secs 秒沒有記錄的區間
+pub fn find_gaps(ord_rec: &OrderRec, min_secs: f64, hours: &TradingHours) -> GapReport {
+	let recs = timed_recs(ord_rec);
+	let seconds: HashSet<i64> = recs.iter().map(|(secs, _)| *secs as i64).collect();
+	let avg_rate = if seconds.is_empty() { 0.0 } else { recs.len() as f64 / seconds.len() as f64 };
+
+	let mut gaps = Vec::new();
+	for (idx, pair) in recs.windows(2).enumerate() {
+		let ((from_secs, from), (to_secs, to)) = (pair[0], pair[1]);
+		let in_hours = hours.overlap(from_secs, to_secs);
+		if in_hours <= min_secs {
+			continue;
+		}
+		let after = recs[idx + 1..].iter().take_while(|(secs, _)| *secs < to_secs + BURST_WINDOW_SECS).count();
+		gaps.push(Gap {
+			from     : Arc::clone(from),
+			to       : Arc::clone(to),
+			secs     : to_secs - from_secs,
+			in_hours,
+			after,
+			burst    : after as f64 > avg_rate * BURST_FACTOR,
+			in_flight: in_flight_at(ord_rec, from_secs),
+		});
+	}
+	GapReport { records: recs.len(), avg_rate, gaps }
+}
+
+/// 中斷檢查的文字報表
+pub fn format_report(report: &GapReport, min_secs: f64) -> String {
+	let mut ret = format!("{} gaps over {}s in trading hours, {} records, {:.2} records per active second\n",
+		report.gaps.len(), min_secs, report.records, report.avg_rate);
+	ret.push_str("From\tTo\tSecs\tInHours\tAfter\tBurst\tInFlight\n");
+	for gap in &report.gaps {
+		ret.push_str(&format!("{} (line {})\t{} (line {})\t{:.3}\t{:.3}\t{}\t{}\t{}\n",
+			gap.from.get_timestamp(), gap.from.get_line_no(), gap.to.get_timestamp(), gap.to.get_line_no(),
+			gap.secs, gap.in_hours, gap.after, if gap.burst { "burst" } else { "" }, gap.in_flight.len()));
+		for order in &gap.in_flight {
+			ret.push_str(&format!("\tin flight: OrdKey {} SorRID {} last {} {} {} (line {})\n",
+				order.ord_key, order.rid, order.last.get_timestamp(), order.last.get_table(), order.last.get_key(), order.last.get_line_no()));
+		}
+	}
+	ret
+}
+
+/// 中斷檢查的 JSON
+pub fn report_json(report: &GapReport) -> Value {
+	let rec_json = |rec: &Rec| json!({ "time": rec.get_timestamp(), "line_no": rec.get_line_no(), "table": rec.get_table(), "key": rec.get_key() });
+	let gaps: Vec<Value> = report.gaps.iter().map(|gap| json!({
+		"from"     : rec_json(&gap.from),
+		"to"       : rec_json(&gap.to),
+		"secs"     : gap.secs,
+		"in_hours" : gap.in_hours,
+		"after"    : gap.after,
+		"burst"    : gap.burst,
+		"in_flight": gap.in_flight.iter().map(|order| json!({
+			"ord_key": order.ord_key,
+			"rid"    : order.rid,
+			"last"   : rec_json(&order.last),
+		})).collect::<Vec<_>>(),
+	})).collect();
+	json!({ "records": report.records, "avg_rate": report.avg_rate, "count": gaps.len(), "gaps": gaps })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parser::tests::parse;
+
+	/// 2024/01/02 當地時間的 timestamp
+	fn at(hour: u32, min: u32) -> f64 {
+		Local.with_ymd_and_hms(2024, 1, 2, hour, min, 0).unwrap().timestamp() as f64
+	}
+
+	#[test]
+	fn trading_hours_parse() {
+		assert!(TradingHours::parse(DEFAULT_TRADING_HOURS).is_ok());
+		assert!(TradingHours::parse(" 08:45-13:45 , ").is_ok());
+		assert!(TradingHours::parse("0845-1345").is_err());
+		assert!(TradingHours::parse("08:45-25:00").is_err());
+		assert!(TradingHours::parse("08:45").is_err());
+		assert!(TradingHours::parse(" , ").is_err());
+	}
+
+	#[test]
+	fn overlap_counts_trading_seconds() {
+		let hours = TradingHours::parse(DEFAULT_TRADING_HOURS).unwrap();
+		assert_eq!(hours.overlap(at(8, 0), at(9, 0)), 15.0 * 60.0);
+		assert_eq!(hours.overlap(at(13, 0), at(16, 0)), 105.0 * 60.0);
+		// 前一天開始的夜盤
+		assert_eq!(hours.overlap(at(4, 0), at(6, 0)), 60.0 * 60.0);
+		assert_eq!(hours.overlap(at(6, 0), at(8, 0)), 0.0);
+	}
+
+	#[test]
+	fn find_gaps_reports_in_flight_orders() {
+		let t = at(9, 0) as i64;
+		let parser = parse(&[
+			format!("Req|R1|TwfNew|{}.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000", t),
+			format!("Ord|O1|TwfOrd|{}.000002|R1|A0001|0|1|1000|", t + 1),
+			format!("Req|R2|TwfNew|{}.000001|1|SorAPI|9A95|456|uid2|u2|100002|2317|S|100|2000", t + 20),
+		]);
+		let hours = TradingHours::parse("00:00-00:00").unwrap();
+		let report = find_gaps(&parser.ord_rec, DEFAULT_GAP_SECS, &hours);
+		assert_eq!(report.records, 3);
+		assert_eq!(report.gaps.len(), 1);
+		let gap = &report.gaps[0];
+		assert_eq!((gap.from.get_key(), gap.to.get_key()), ("O1", "R2"));
+		assert_eq!(gap.secs.round(), 19.0);
+		let in_flight: Vec<&str> = gap.in_flight.iter().map(|order| order.ord_key.as_str()).collect();
+		assert_eq!(in_flight, ["O1"]);
+		assert_eq!(gap.in_flight[0].rid, "100001");
+
+		let none = find_gaps(&parser.ord_rec, DEFAULT_GAP_SECS, &TradingHours::parse("13:00-14:00").unwrap());
+		assert!(none.gaps.is_empty());
+	}
+}
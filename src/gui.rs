@@ -1,51 +1,463 @@
 // 引入所需的 crate
 use rfd::FileDialog;
 use std::fs;
-use crate::parser::Parser;
-use crate::fileread::read_data_log;
-use std::io::BufReader;
+use crate::parser::{Parser, Rec, LOG_TABLE};
+use crate::fileread::{detect_encoding, read_log_file_with_progress};
+use crate::pki::{self, PkiLayout, PkiLine, PkiRecord, PkiWriter};
+use crate::util::csv_field;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
 
 // 使用 slint! 巨集來定義 GUI
 slint::slint! {
-    import { Button, ScrollView } from "std-widgets.slint";
+    import { Button, ComboBox, LineEdit, ProgressIndicator, ScrollView, StandardTableView, TabWidget } from "std-widgets.slint";
+
+    // 記錄的一個欄位，matched 為符合搜尋條件的欄位
+    export struct FieldLine {
+        name: string,
+        value: string,
+        matched: bool,
+    }
+
+    // 委託明細中的一筆 Req/Ord 記錄，raw/log/digsgn 預設摺疊
+    export struct RecordDetail {
+        title: string,
+        fields: [FieldLine],
+        name_width: length,
+        raw: string,
+        log: string,
+        log_lines: int,
+        digsgn: string,
+    }
+
+    // 長條圖的一條: value 為 0..1 的高度比例，text 為點選時顯示的說明
+    export struct Bar {
+        value: float,
+        text: string,
+    }
+
+    component BarChart inherits VerticalLayout {
+        in property <string> title;
+        in property <[Bar]> bars;
+        in property <string> first_label;
+        in property <string> last_label;
+        callback bar_clicked(string);
+
+        spacing: 2px;
+        Text {
+            text: root.title;
+            font-weight: 700;
+        }
+        Rectangle {
+            vertical-stretch: 1;
+            min-height: 80px;
+            border-color: #ccc;
+            border-width: 1px;
+            HorizontalLayout {
+                padding: 2px;
+                spacing: 1px;
+                for bar in root.bars : Rectangle {
+                    Rectangle {
+                        y: parent.height * (1 - bar.value);
+                        height: parent.height * bar.value;
+                        background: area.has-hover ? #2a70b9 : #4a90d9;
+                    }
+                    area := TouchArea {
+                        clicked => { root.bar_clicked(bar.text) }
+                    }
+                }
+            }
+        }
+        HorizontalLayout {
+            Text {
+                text: root.first_label;
+                font-size: 11px;
+                color: #666;
+            }
+            Text {
+                horizontal-stretch: 1;
+                horizontal-alignment: right;
+                text: root.last_label;
+                font-size: 11px;
+                color: #666;
+            }
+        }
+    }
 
     export component AppWindow inherits Window {
-        title: "SOR Log Parser - PKI Mode";
-        width: 1024px;
+        title: "SOR Log Parser";
+        width: 1280px;
         height: 768px;
 
         in-out property <[string]> column_data: [];
-        in-out property <[[string]]> row_data: [[]];
-        in-out property <string> status_text: "Ready";
+        in-out property <[TableColumn]> table_columns: [];
+        in-out property <[[StandardListViewItem]]> row_data: [];
+        in-out property <int> copy_column: 0;
+        in-out property <string> status_text: "Open a directory or files, or drop them on the window";
+        in-out property <string> encoding: "auto";
+        in-out property <bool> parsing: false;
+        in-out property <float> progress: 0;
+        in-out property <int> selected_row: -1;
+        in-out property <string> detail_title: "Select a row to show the order";
+        in-out property <[RecordDetail]> detail_records: [];
+
+        // Dashboard: dashboard_day 0 為全部日期
+        in-out property <[string]> dashboard_days: ["All days"];
+        in-out property <int> dashboard_day: 0;
+        in-out property <string> dashboard_caption: "Click a bar to show its value";
+        in-out property <[[StandardListViewItem]]> day_counts: [];
+        in-out property <[[StandardListViewItem]]> failed_tables: [];
+        in-out property <[Bar]> flow_bars: [];
+        in-out property <string> flow_first: "";
+        in-out property <string> flow_last: "";
+        in-out property <[Bar]> latency_bars: [];
+        in-out property <string> latency_summary: "";
+        in-out property <[[StandardListViewItem]]> top_users: [];
+        in-out property <[[StandardListViewItem]]> top_sessions: [];
+
+        // 搜尋條件: 0 = PKI 記錄, 1 = 委託
+        in-out property <int> view_mode: 0;
+        in-out property <string> conditions: "";
+        in-out property <string> condition_error: "";
+        in-out property <[string]> table_names: [];
+        in-out property <[string]> field_names: [];
+        in-out property <string> picked_table: "";
+        in-out property <string> picked_field: "";
+        in-out property <string> time_from: "";
+        in-out property <string> time_to: "";
+        in-out property <string> filter_error: "";
 
         callback open_dir_dialog();
+        callback open_files_dialog();
+        callback cancel_parse();
+        callback row_selected(int);
+        callback table_picked(string);
+        callback insert_condition();
+        callback conditions_edited();
+        callback run_search();
+        callback filters_changed();
+        callback column_filter_edited(int, string);
+        callback sort_rows(int, bool);
+        callback copy_cell();
+        callback copy_row();
+        callback export_pki();
+        callback export_csv();
+        callback export_json();
+        callback dashboard_day_changed();
 
         VerticalLayout {
-            Button {
-                text: "Select Directory to Parse";
-                clicked => { root.open_dir_dialog() }
-            }
-            Text {
-                text: root.status_text;
-                color: #666;
-                font-size: 12px;
+            spacing: 3px;
+            HorizontalLayout {
+                spacing: 5px;
+                Button {
+                    text: "Open Directory";
+                    enabled: !root.parsing;
+                    clicked => { root.open_dir_dialog() }
+                }
+                Button {
+                    text: "Open Files";
+                    enabled: !root.parsing;
+                    clicked => { root.open_files_dialog() }
+                }
+                Text {
+                    text: "Encoding";
+                    vertical-alignment: center;
+                }
+                ComboBox {
+                    model: ["auto", "BIG5", "GB", "JP", "UTF8"];
+                    current-value <=> root.encoding;
+                    enabled: !root.parsing;
+                }
+                Button {
+                    text: "Cancel";
+                    enabled: root.parsing;
+                    clicked => { root.cancel_parse() }
+                }
+                ProgressIndicator {
+                    horizontal-stretch: 1;
+                    progress: root.progress;
+                }
+                Button {
+                    text: "Export PKI";
+                    enabled: !root.parsing;
+                    clicked => { root.export_pki() }
+                }
+                Button {
+                    text: "Export CSV";
+                    enabled: !root.parsing;
+                    clicked => { root.export_csv() }
+                }
+                Button {
+                    text: "Export JSON";
+                    enabled: !root.parsing;
+                    clicked => { root.export_json() }
+                }
             }
-            ScrollView {
-                VerticalLayout {
-                    // Header
-                    HorizontalLayout {
-                        padding: 5px;
-                        for header_text in column_data : Text {
-                            text: header_text;
-                            width: 150px;
+            TabWidget {
+                Tab {
+                    title: "Records";
+                    VerticalLayout {
+                        spacing: 3px;
+                        // 條件輸入: 表名、欄位可由選單插入
+                        HorizontalLayout {
+                            spacing: 5px;
+                            ComboBox {
+                                model: ["PKI records", "Orders"];
+                                current-index <=> root.view_mode;
+                                selected => { root.run_search() }
+                            }
+                            ComboBox {
+                                model: root.table_names;
+                                current-value <=> root.picked_table;
+                                selected(name) => { root.table_picked(name) }
+                            }
+                            ComboBox {
+                                model: root.field_names;
+                                current-value <=> root.picked_field;
+                            }
+                            Button {
+                                text: "Insert";
+                                enabled: root.picked_table != "" && root.picked_field != "";
+                                clicked => { root.insert_condition() }
+                            }
+                            LineEdit {
+                                horizontal-stretch: 1;
+                                placeholder-text: "Table:Field:Value  (',' = AND, '|' = OR)";
+                                text <=> root.conditions;
+                                edited => { root.conditions_edited() }
+                                accepted => { root.run_search() }
+                            }
+                            Button {
+                                text: "Search";
+                                enabled: !root.parsing && root.condition_error == "";
+                                clicked => { root.run_search() }
+                            }
+                        }
+                        // 時間區間: HH:MM:SS 或 YYYYMMDD HH:MM:SS
+                        HorizontalLayout {
+                            spacing: 5px;
+                            Text {
+                                text: "Time from";
+                                vertical-alignment: center;
+                            }
+                            LineEdit {
+                                width: 200px;
+                                placeholder-text: "HH:MM:SS";
+                                text <=> root.time_from;
+                                edited => { root.filters_changed() }
+                            }
+                            Text {
+                                text: "to";
+                                vertical-alignment: center;
+                            }
+                            LineEdit {
+                                width: 200px;
+                                placeholder-text: "HH:MM:SS";
+                                text <=> root.time_to;
+                                edited => { root.filters_changed() }
+                            }
+                            Text {
+                                horizontal-stretch: 1;
+                                text: root.condition_error + (root.condition_error != "" && root.filter_error != "" ? "; " : "") + root.filter_error;
+                                color: #c00;
+                                vertical-alignment: center;
+                            }
+                        }
+                        HorizontalLayout {
+                            spacing: 5px;
+                            Text {
+                                horizontal-stretch: 1;
+                                text: root.status_text;
+                                color: #666;
+                                font-size: 12px;
+                                vertical-alignment: center;
+                            }
+                            ComboBox {
+                                model: root.column_data;
+                                current-index <=> root.copy_column;
+                            }
+                            Button {
+                                text: "Copy Cell";
+                                enabled: root.selected_row >= 0;
+                                clicked => { root.copy_cell() }
+                            }
+                            Button {
+                                text: "Copy Row";
+                                enabled: root.selected_row >= 0;
+                                clicked => { root.copy_row() }
+                            }
+                        }
+                        HorizontalLayout {
+                            VerticalLayout {
+                                horizontal-stretch: 3;
+                                // 各欄的文字過濾
+                                HorizontalLayout {
+                                    spacing: 2px;
+                                    for header_text[i] in root.column_data : LineEdit {
+                                        placeholder-text: header_text;
+                                        edited(text) => { root.column_filter_edited(i, text) }
+                                    }
+                                }
+                                // 只建立看得到的列；點標題排序，拖曳標題邊界調整欄寬，右鍵複製該列
+                                StandardTableView {
+                                    columns <=> root.table_columns;
+                                    rows: root.row_data;
+                                    current-row <=> root.selected_row;
+                                    current-row-changed(row) => { root.row_selected(row) }
+                                    sort-ascending(column) => { root.sort_rows(column, true) }
+                                    sort-descending(column) => { root.sort_rows(column, false) }
+                                    row-pointer-event(row, event, position) => {
+                                        if (event.button == PointerEventButton.right && event.kind == PointerEventKind.down) {
+                                            root.selected_row = row;
+                                            root.row_selected(row);
+                                            root.copy_row();
+                                        }
+                                    }
+                                }
+                            }
+                            // 選取列的委託明細
+                            ScrollView {
+                                horizontal-stretch: 2;
+                                VerticalLayout {
+                                    padding: 5px;
+                                    spacing: 6px;
+                                    alignment: start;
+                                    Text {
+                                        text: root.detail_title;
+                                        font-weight: 700;
+                                    }
+                                    for rec in root.detail_records : detail := VerticalLayout {
+                                        in-out property <bool> show_raw: false;
+                                        in-out property <bool> show_log: false;
+                                        spacing: 2px;
+                                        Text {
+                                            text: rec.title;
+                                            font-weight: 700;
+                                            color: #036;
+                                        }
+                                        for field in rec.fields : HorizontalLayout {
+                                            spacing: 4px;
+                                            Text {
+                                                width: rec.name_width;
+                                                text: (field.matched ? "* " : "  ") + field.name;
+                                                font-size: 11px;
+                                                font-weight: field.matched ? 700 : 400;
+                                                color: field.matched ? #c00 : #000;
+                                            }
+                                            Text {
+                                                text: "= " + field.value;
+                                                font-size: 11px;
+                                                font-weight: field.matched ? 700 : 400;
+                                                color: field.matched ? #c00 : #000;
+                                            }
+                                        }
+                                        Text {
+                                            text: (detail.show_raw ? "▾ " : "▸ ") + "raw";
+                                            color: #036;
+                                            font-size: 11px;
+                                            TouchArea {
+                                                clicked => { detail.show_raw = !detail.show_raw; }
+                                            }
+                                        }
+                                        if detail.show_raw : Text {
+                                            text: rec.raw;
+                                            color: #666;
+                                            font-size: 11px;
+                                            wrap: char-wrap;
+                                        }
+                                        if rec.log_lines > 0 : Text {
+                                            text: (detail.show_log ? "▾ " : "▸ ") + "log (" + rec.log_lines + " lines)";
+                                            color: #036;
+                                            font-size: 11px;
+                                            TouchArea {
+                                                clicked => { detail.show_log = !detail.show_log; }
+                                            }
+                                        }
+                                        if detail.show_log : Text {
+                                            text: rec.log;
+                                            color: #666;
+                                            font-size: 11px;
+                                            wrap: char-wrap;
+                                        }
+                                        if rec.digsgn != "" : Text {
+                                            text: "  digsgn " + rec.digsgn;
+                                            color: #666;
+                                            font-size: 11px;
+                                        }
+                                    }
+                                }
+                            }
                         }
                     }
-                    // Rows
-                    for row in row_data : HorizontalLayout {
+                }
+                Tab {
+                    title: "Dashboard";
+                    VerticalLayout {
+                        spacing: 5px;
                         padding: 5px;
-                        for cell_text in row : Text {
-                            text: cell_text;
-                            width: 150px;
+                        HorizontalLayout {
+                            spacing: 5px;
+                            Text {
+                                text: "Day";
+                                vertical-alignment: center;
+                            }
+                            ComboBox {
+                                model: root.dashboard_days;
+                                current-index <=> root.dashboard_day;
+                                selected => { root.dashboard_day_changed() }
+                            }
+                            Text {
+                                horizontal-stretch: 1;
+                                text: root.dashboard_caption;
+                                color: #666;
+                                vertical-alignment: center;
+                            }
+                        }
+                        HorizontalLayout {
+                            spacing: 5px;
+                            StandardTableView {
+                                horizontal-stretch: 3;
+                                min-height: 150px;
+                                columns: [
+                                    { title: "Day" }, { title: "Tables" }, { title: "Reqs" }, { title: "Ords" },
+                                    { title: "Deals" }, { title: "Failed (99)" },
+                                ];
+                                rows: root.day_counts;
+                            }
+                            StandardTableView {
+                                horizontal-stretch: 1;
+                                min-height: 150px;
+                                columns: [{ title: "Failed table" }, { title: "Orders" }];
+                                rows: root.failed_tables;
+                            }
+                        }
+                        BarChart {
+                            vertical-stretch: 1;
+                            title: "Requests per second (peak per bar)";
+                            bars: root.flow_bars;
+                            first_label: root.flow_first;
+                            last_label: root.flow_last;
+                            bar_clicked(text) => { root.dashboard_caption = text; }
+                        }
+                        HorizontalLayout {
+                            vertical-stretch: 1;
+                            spacing: 5px;
+                            BarChart {
+                                horizontal-stretch: 2;
+                                title: "Req to first Ord latency: " + root.latency_summary;
+                                bars: root.latency_bars;
+                                bar_clicked(text) => { root.dashboard_caption = text; }
+                            }
+                            StandardTableView {
+                                horizontal-stretch: 1;
+                                columns: [{ title: "User" }, { title: "Reqs" }];
+                                rows: root.top_users;
+                            }
+                            StandardTableView {
+                                horizontal-stretch: 1;
+                                columns: [{ title: "Session" }, { title: "Reqs" }];
+                                rows: root.top_sessions;
+                            }
                         }
                     }
                 }
@@ -54,123 +466,1059 @@ slint::slint! {
     }
 }
 
-use slint::{ModelRc, SharedString, VecModel};
+use arboard::Clipboard;
+use chrono::{Local, TimeZone};
+use i_slint_backend_winit::winit::event::WindowEvent;
+use i_slint_backend_winit::{WinitWindowAccessor, WinitWindowEventResult};
+use serde::{Deserialize, Serialize};
+use slint::{Model, ModelNotify, ModelRc, ModelTracker, SharedString, StandardListViewItem, TableColumn, VecModel};
+use std::any::Any;
+use std::cell::RefCell;
+use std::cmp;
+use std::collections::{BTreeMap, BTreeSet, LinkedList};
 use std::rc::Rc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::thread;
+use std::time::Duration;
 use regex::Regex;
 
+const ORDER_HEADERS: [&str; 6] = ["Time", "OrdKey", "SorRID", "OrdNo", "Table", "Status"];
+
+/// Dashboard 列出的使用者/連線數
+const TOP_N: usize = 10;
+/// 流量圖最多的長條數，超過時每條涵蓋多秒並顯示其中的最大值
+const MAX_FLOW_BARS: i64 = 120;
+/// 延遲分佈的區間上限 (毫秒)
+const LATENCY_BUCKETS: [(f64, &str); 8] = [
+    (1.0, "<1ms"), (5.0, "1-5ms"), (10.0, "5-10ms"), (50.0, "10-50ms"),
+    (100.0, "50-100ms"), (500.0, "100-500ms"), (1000.0, "0.5-1s"), (f64::INFINITY, ">=1s"),
+];
+
+/// 超過此長度的欄位值 (例如 DigsgnHash) 在表格中只顯示開頭
+const MAX_CELL_CHARS: usize = 64;
+const TRUNCATED_CHARS: usize = 24;
+/// 明細中欄位名每個字的寬度 (px)，用來對齊欄位值
+const NAME_CHAR_WIDTH: f32 = 7.0;
+
+/// GUI 的設定，離開時存在 HOME 下，下次啟動時讀回
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+struct GuiConfig {
+    last_dir: String,
+    encoding: String,
+    view_mode: i32,
+    conditions: String,
+}
+
+impl Default for GuiConfig {
+    fn default() -> GuiConfig {
+        GuiConfig {
+            last_dir: String::new(),
+            encoding: "auto".to_string(),
+            view_mode: 0,
+            conditions: String::new(),
+        }
+    }
+}
+
+impl GuiConfig {
+    /// 設定檔放在 HOME 下，沒有 HOME 時放在目前目錄
+    fn path() -> String {
+        match std::env::var("HOME") {
+            Ok(home) => format!("{}/.sor_logparser_gui.toml", home),
+            Err(_) => ".sor_logparser_gui.toml".to_string(),
+        }
+    }
+    fn load() -> GuiConfig {
+        fs::read_to_string(Self::path()).ok()
+            .and_then(|content| toml::from_str(&content).ok())
+            .unwrap_or_default()
+    }
+    fn save(&self) {
+        if let Ok(content) = toml::to_string(self) {
+            if let Err(e) = fs::write(Self::path(), content) {
+                println!("cannot save {}: {}", Self::path(), e);
+            }
+        }
+    }
+}
+
+/// 目前的查詢: 顯示PKI記錄或委託，及搜尋條件 (空字串為全部)
+#[derive(Clone, Default)]
+struct Query {
+    orders: bool,
+    conditions: String,
+}
+
+/// 表格中的一列，key 為 PKI 列的 ReqKey 或委託的 OrdKey
+struct ResultRow {
+    cells: Vec<String>,
+    parser_idx: usize,
+    key: String,
+    stamp: String, // YYYYMMDDHHMMSS, 用於時間區間過濾
+}
+
+/// 解析後的 Parser 保留下來供查詢及明細；由背景解析執行緒寫入，UI 執行緒讀取
+#[derive(Default)]
+struct ParsedData {
+    parsers: Vec<Parser>,
+    sources: Vec<String>, // 每個 Parser 的 SorReqOrd.log 路徑
+    tables: BTreeMap<String, BTreeSet<String>>, // 已知的表名-欄位名
+    query: Query,
+    rows: Vec<ResultRow>,
+    column_filters: Vec<String>,
+    sort: Option<(usize, bool)>, // 排序的欄及是否遞增
+    visible: Vec<usize>, // 目前顯示的列 (rows 的 index)
+}
+
+/// 鎖住 data；持有的執行緒中途失敗時仍取用其內容，不讓之後的操作都失敗
+fn lock_data(data: &Mutex<ParsedData>) -> MutexGuard<'_, ParsedData> {
+    data.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// 表格的 model: 列只在顯示時才由 ParsedData 取出，不必為所有列建立 VecModel
+struct RowsModel {
+    data: Arc<Mutex<ParsedData>>,
+    notify: ModelNotify,
+}
+
+impl Model for RowsModel {
+    type Data = ModelRc<StandardListViewItem>;
+
+    fn row_count(&self) -> usize {
+        lock_data(&self.data).visible.len()
+    }
+
+    fn row_data(&self, row: usize) -> Option<Self::Data> {
+        let data = lock_data(&self.data);
+        let cells: Vec<StandardListViewItem> = data.rows[*data.visible.get(row)?].cells.iter()
+            .map(|c| StandardListViewItem::from(display_cell(c)))
+            .collect();
+        Some(Rc::new(VecModel::from(cells)).into())
+    }
+
+    fn model_tracker(&self) -> &dyn ModelTracker {
+        &self.notify
+    }
+
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+}
+
+/// 過長的值只顯示開頭及長度，完整內容可複製或在明細中查看
+fn display_cell(cell: &str) -> SharedString {
+    let len = cell.chars().count();
+    if len > MAX_CELL_CHARS {
+        let head: String = cell.chars().take(TRUNCATED_CHARS).collect();
+        format!("{}…({})", head, len).into()
+    } else {
+        cell.into()
+    }
+}
+
+/// 兩者皆為數字時依數值比較，否則依字串比較
+fn compare_cells(a: &str, b: &str) -> cmp::Ordering {
+    match (a.trim().parse::<f64>(), b.trim().parse::<f64>()) {
+        (Ok(x), Ok(y)) => x.partial_cmp(&y).unwrap_or(cmp::Ordering::Equal),
+        _ => a.cmp(b),
+    }
+}
+
+fn format_bytes(bytes: u64) -> String {
+    format!("{:.1} MB", bytes as f64 / (1024.0 * 1024.0))
+}
+
+fn rec_stamp(rec: &Rec) -> String {
+    format!("{}{}", rec.get_date(), rec.get_time())
+}
+
+/// 驗證條件語法，並檢查表名、欄位名是否存在於已解析的記錄中
+fn validate_conditions(tables: &BTreeMap<String, BTreeSet<String>>, condstr: &str) -> Result<(), String> {
+    if condstr.trim().is_empty() {
+        return Ok(());
+    }
+    for cond in condstr.split([',', '|']) {
+        let toks: Vec<&str> = cond.trim().split(':').collect();
+        if toks.len() < 3 {
+            return Err(format!("{} is not correct! please specify TableName:FieldName:Value", cond.trim()));
+        }
+        if tables.is_empty() {
+            continue;
+        }
+        match tables.get(toks[0]) {
+            Some(fields) if !fields.contains(toks[1]) => return Err(format!("there is no {} field in {}", toks[1], toks[0])),
+            Some(_) => {},
+            None => return Err(format!("there is no {} table", toks[0])),
+        }
+    }
+    Ok(())
+}
+
+/// 將時間輸入正規化為數字: HH:MM:SS 為 6 碼以內，含日期的為 YYYYMMDDHHMMSS 的前段
+fn parse_time_bound(input: &str) -> Result<String, String> {
+    let digits: String = input.chars().filter(|c| !matches!(c, ':' | ' ' | '/' | '-')).collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) || digits.len() > 14 || (digits.len() > 6 && digits.len() < 8) {
+        return Err(format!("{} is not a time, use HH:MM:SS or YYYYMMDD HH:MM:SS", input.trim()));
+    }
+    Ok(digits)
+}
+
+/// 時間是否在區間內，區間兩端只比較輸入的位數
+fn in_time_range(stamp: &str, from: &str, to: &str) -> bool {
+    let part = |bound: &str| -> &str {
+        if bound.len() <= 6 {
+            let time = stamp.get(8..).unwrap_or("");
+            time.get(..bound.len()).unwrap_or(time)
+        } else {
+            stamp.get(..bound.len()).unwrap_or(stamp)
+        }
+    };
+    (from.is_empty() || part(from) >= from) && (to.is_empty() || part(to) <= to)
+}
+
+/// 產生符合條件的PKI記錄，同 CLI 的 --pki
+fn pki_lines(parser: &mut Parser, conditions: &str) -> Result<Vec<PkiLine>, String> {
+    if conditions.is_empty() {
+        Ok(parser.get_pki_records())
+    } else {
+        parser.find_by_conditions(conditions, "", &true, true, true)?;
+        Ok(parser.take_pki_lines())
+    }
+}
+
+/// 依查詢由一個 Parser 產生表格列，條件格式錯誤時回傳說明
+fn query_rows(parser: &mut Parser, parser_idx: usize, query: &Query, layout: &PkiLayout) -> Result<Vec<ResultRow>, String> {
+    let mut rows = Vec::new();
+    if query.orders {
+        let lists: Vec<LinkedList<Arc<Rec>>> = if query.conditions.is_empty() {
+            parser.ord_rec.ords.keys().map(|key| parser.ord_rec.get_target_ordlist(key)).collect()
+        } else {
+            parser.search(&query.conditions, &true, true)?.into_iter().collect()
+        };
+        for list in lists {
+            let (Some(first), Some(ord)) = (list.front(), list.iter().find(|r| !r.is_req())) else {
+                continue;
+            };
+            let info = parser.ord_rec.get_ord_summary(&list);
+            rows.push(ResultRow {
+                cells: vec![
+                    first.get_timestamp(), ord.get_key().to_string(), info.rid().to_string(),
+                    info.ordno().to_string(), first.get_table().to_string(), info.status().to_string(),
+                ],
+                parser_idx,
+                key: ord.get_key().to_string(),
+                stamp: rec_stamp(first),
+            });
+        }
+    } else {
+        let lines = pki_lines(parser, &query.conditions)?;
+        // 剔除記錄在匯出PKI檔時才寫入 .rej，這裡不保留
+        parser.take_pki_rejects("");
+        for pki_line in lines {
+            if let Some(rec) = PkiRecord::parse(layout, &pki_line.line) {
+                let stamp = parser.ord_rec.reqs.get(&pki_line.req_key).map(|r| rec_stamp(r)).unwrap_or_default();
+                rows.push(ResultRow { cells: rec.values, parser_idx, key: pki_line.req_key, stamp });
+            }
+        }
+    }
+    rows.sort_by(|a, b| a.stamp.cmp(&b.stamp));
+    Ok(rows)
+}
+
+/// 記下 Parser 中的表名、欄位名，供條件驗證及選單使用
+fn collect_tables(tables: &mut BTreeMap<String, BTreeSet<String>>, parser: &Parser) {
+    for (name, tab) in &parser.ord_rec.tables {
+        tables.entry(name.clone()).or_default().extend(tab.recs.iter().skip(3).cloned());
+    }
+    // log 欄位以 log:Tag:Value 搜尋
+    let log_tags = parser.ord_rec.log_tags();
+    if !log_tags.is_empty() {
+        tables.entry(LOG_TABLE.to_string()).or_default().extend(log_tags.into_keys());
+    }
+}
+
+fn set_columns(ui: &AppWindow, data: &mut ParsedData, layout: &PkiLayout) {
+    let headers: Vec<SharedString> = if data.query.orders {
+        ORDER_HEADERS.iter().map(|h| (*h).into()).collect()
+    } else {
+        layout.headers().iter().map(|h| h.as_str().into()).collect()
+    };
+    let columns: Vec<TableColumn> = headers.iter().map(|title| {
+        let mut column = TableColumn::default();
+        column.title = title.clone();
+        column.min_width = 80.0;
+        column.horizontal_stretch = 1.0;
+        column
+    }).collect();
+    data.column_filters = vec![String::new(); headers.len()];
+    data.sort = None;
+    ui.set_column_data(Rc::new(VecModel::from(headers)).into());
+    ui.set_table_columns(Rc::new(VecModel::from(columns)).into());
+    ui.set_copy_column(0);
+}
+
+/// 依時間區間及各欄過濾重建顯示的列
+fn refresh_rows(ui: &AppWindow, data: &mut ParsedData) {
+    let (from, to) = match (parse_time_bound(&ui.get_time_from()), parse_time_bound(&ui.get_time_to())) {
+        (Ok(from), Ok(to)) => {
+            ui.set_filter_error("".into());
+            (from, to)
+        },
+        (Err(e), _) | (_, Err(e)) => {
+            ui.set_filter_error(e.into());
+            (String::new(), String::new())
+        }
+    };
+    let filters: Vec<(usize, String)> = data.column_filters.iter().enumerate()
+        .filter(|(_, f)| !f.is_empty())
+        .map(|(i, f)| (i, f.to_lowercase()))
+        .collect();
+    data.visible = data.rows.iter().enumerate()
+        .filter(|(_, row)| in_time_range(&row.stamp, &from, &to))
+        .filter(|(_, row)| filters.iter().all(|(i, f)| row.cells.get(*i).is_some_and(|c| c.to_lowercase().contains(f.as_str()))))
+        .map(|(i, _)| i)
+        .collect();
+    if let Some((column, ascending)) = data.sort {
+        let rows = &data.rows;
+        let cell = |i: usize| rows[i].cells.get(column).map(|c| c.as_str()).unwrap_or("");
+        data.visible.sort_by(|a, b| {
+            let order = compare_cells(cell(*a), cell(*b));
+            if ascending { order } else { order.reverse() }
+        });
+    }
+    if ui.get_selected_row() >= data.visible.len() as i32 {
+        ui.set_selected_row(-1);
+    }
+    // model 讀取時才取列，這裡只需通知表格重新讀取
+    if let Some(model) = ui.get_row_data().as_any().downcast_ref::<RowsModel>() {
+        model.notify.reset();
+    }
+}
+
+/// 選取列的各欄完整內容
+fn selected_cells(ui: &AppWindow, data: &ParsedData) -> Option<Vec<String>> {
+    let row = usize::try_from(ui.get_selected_row()).ok()?;
+    data.visible.get(row).map(|i| data.rows[*i].cells.clone())
+}
+
+/// 複製文字到剪貼簿；剪貼簿物件保留下來，讓部分平台在複製後內容仍有效
+fn copy_to_clipboard(ui: &AppWindow, clipboard: &RefCell<Option<Clipboard>>, text: String, what: &str) {
+    let mut clipboard = clipboard.borrow_mut();
+    if clipboard.is_none() {
+        *clipboard = Clipboard::new().ok();
+    }
+    let result = match clipboard.as_mut() {
+        Some(cb) => cb.set_text(text).map_err(|e| e.to_string()),
+        None => Err("clipboard is not available".to_string()),
+    };
+    match result {
+        Ok(()) => ui.set_status_text(format!("{} copied to clipboard", what).into()),
+        Err(e) => ui.set_status_text(format!("cannot copy {}: {}", what, e).into()),
+    }
+}
+
+/// 更新表名選單
+fn set_table_names(ui: &AppWindow, data: &ParsedData) {
+    let names: Vec<SharedString> = data.tables.keys().map(|n| n.as_str().into()).collect();
+    ui.set_table_names(Rc::new(VecModel::from(names)).into());
+}
+
+/// 找出要解析的 log: 檔案直接使用；目錄則取其中的 SorReqOrd.log 及各日期子目錄 (YYYYMMDD) 的 SorReqOrd.log
+fn collect_logs(path: &Path) -> Vec<PathBuf> {
+    if path.is_file() {
+        return vec![path.to_path_buf()];
+    }
+    let mut logs = Vec::new();
+    let own_log = path.join("SorReqOrd.log");
+    if own_log.is_file() {
+        logs.push(own_log);
+    }
+    let date_regex = Regex::new(r"^\d{8}$").unwrap();
+    let mut date_dirs = Vec::new();
+    if let Ok(entries) = fs::read_dir(path) {
+        for entry in entries.flatten() {
+            if let Ok(metadata) = entry.metadata() {
+                if metadata.is_dir() {
+                    if let Some(dir_name) = entry.file_name().to_str() {
+                        if date_regex.is_match(dir_name) {
+                            date_dirs.push(entry.path());
+                        }
+                    }
+                }
+            }
+        }
+    }
+    date_dirs.sort();
+    logs.extend(date_dirs.iter().map(|dir| dir.join("SorReqOrd.log")).filter(|log| log.is_file()));
+    logs
+}
+
+/// 背景解析結束時 (含中途失敗) 恢復 parsing 狀態，讓使用者可以再次開啟或查詢
+struct ParsingGuard(slint::Weak<AppWindow>);
+
+impl Drop for ParsingGuard {
+    fn drop(&mut self) {
+        let failed = thread::panicking();
+        let _ = self.0.upgrade_in_event_loop(move |ui| {
+            if failed {
+                ui.set_status_text("Parsing failed".into());
+                ui.set_progress(0.0);
+            }
+            ui.set_parsing(false);
+        });
+    }
+}
+
+/// 在背景執行緒依序解析各 log，encoding 為 auto 時逐檔偵測
+/// 每解析完一個 log 即依目前的查詢加入表格，cancel 被設定時停止
+fn parse_logs(ui_handle: slint::Weak<AppWindow>, logs: Vec<PathBuf>, encoding: String, layout: PkiLayout, data: Arc<Mutex<ParsedData>>, cancel: Arc<AtomicBool>) {
+    let logs: Vec<(PathBuf, u64)> = logs.into_iter()
+        .filter_map(|path| fs::metadata(&path).ok().map(|m| (path, m.len())))
+        .collect();
+    let _parsing = ParsingGuard(ui_handle.clone());
+    let total_bytes: u64 = logs.iter().map(|(_, size)| size).sum::<u64>().max(1);
+    let mut done_bytes: u64 = 0;
+    let mut parsed_logs = 0;
+    let mut cancelled = false;
+
+    for (i, (log_path, size)) in logs.iter().enumerate() {
+        let name = source_name(&log_path.to_string_lossy());
+        let log_encoding = if encoding == "auto" { detect_encoding(log_path) } else { encoding.as_str() };
+        let mut parser = Parser::new();
+        parser.set_pki_layout(layout.clone());
+        let completed = read_log_file_with_progress(log_path, &mut parser, log_encoding, &mut |bytes, recs| {
+            let text = format!("Parsing {} as {} ({}/{}): {} read, {} records", name, log_encoding, i + 1, logs.len(), format_bytes(bytes), recs);
+            let progress = (done_bytes + bytes) as f32 / total_bytes as f32;
+            let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+                ui.set_status_text(text.into());
+                ui.set_progress(progress);
+            });
+            !cancel.load(Ordering::Relaxed)
+        });
+        match completed {
+            Ok(true) => {},
+            Ok(false) => {
+                cancelled = true;
+                break;
+            },
+            Err(_) => continue,
+        }
+        done_bytes += size;
+        parsed_logs += 1;
+
+        // 解析期間只有這個執行緒加入 Parser，查詢時不必鎖住 data
+        let (parser_idx, query) = {
+            let data = lock_data(&data);
+            (data.parsers.len(), data.query.clone())
+        };
+        // 條件已在查詢時檢查過，這裡不再顯示錯誤
+        let rows = query_rows(&mut parser, parser_idx, &query, &layout).unwrap_or_default();
+        {
+            let mut data = lock_data(&data);
+            data.rows.extend(rows);
+            collect_tables(&mut data.tables, &parser);
+            data.parsers.push(parser);
+            data.sources.push(log_path.to_string_lossy().to_string());
+        }
+        let data = data.clone();
+        let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+            let mut data = lock_data(&data);
+            set_table_names(&ui, &data);
+            refresh_rows(&ui, &mut data);
+        });
+    }
+
+    let data = data.clone();
+    let _ = ui_handle.upgrade_in_event_loop(move |ui| {
+        let data = lock_data(&data);
+        let text = if cancelled {
+            format!("Cancelled, parsed {} of {} logs, {} rows", parsed_logs, logs.len(), data.rows.len())
+        } else if data.rows.is_empty() {
+            "No records found".to_string()
+        } else {
+            format!("Parsed {} logs, {} rows", parsed_logs, data.rows.len())
+        };
+        ui.set_status_text(text.into());
+        ui.set_progress(if cancelled { 0.0 } else { 1.0 });
+        refresh_dashboard(&ui, &data);
+    });
+}
+
+/// 取得表格列對應的整筆委託: orders 時 key 為 OrdKey，否則為 PKI 列的 ReqKey
+fn order_list(parser: &Parser, orders: bool, key: &str) -> LinkedList<Arc<Rec>> {
+    let ord_rec = &parser.ord_rec;
+    if orders {
+        ord_rec.get_target_ordlist(key)
+    } else {
+        match ord_rec.get_ord_key(key) {
+            Some(ord_key) => ord_rec.get_target_ordlist(ord_key),
+            None => ord_rec.reqs.get(key).cloned().into_iter().collect(),
+        }
+    }
+}
+
+/// 取得表格列對應的整筆委託明細，conditions 符合的欄位會被標示
+/// 回傳 (委託摘要, 每筆 Req/Ord 記錄)
+fn order_details(parser: &Parser, orders: bool, key: &str, conditions: &str) -> (String, Vec<RecordDetail>) {
+    let ord_rec = &parser.ord_rec;
+    let list = order_list(parser, orders, key);
+    let summary = format!("{}", ord_rec.get_ord_summary(&list)).trim().trim_matches('=').trim().to_string();
+    let records = list.iter().map(|rec| {
+        let kind = if rec.is_req() { "Req" } else { "Ord" };
+        let matched = ord_rec.matched_fields(rec, conditions);
+        let fields = ord_rec.get_fields(rec);
+        let name_chars = fields.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+        let fields: Vec<FieldLine> = fields.into_iter()
+            .map(|(name, value)| FieldLine { matched: matched.contains(&name), name: name.into(), value: value.into() })
+            .collect();
+        let log_lines = rec.get_log_lines();
+        RecordDetail {
+            title: format!("{} {} {} {} {}", rec.get_timestamp(), kind, rec.get_key(), rec.get_table(), rec.get_label()).into(),
+            fields: Rc::new(VecModel::from(fields)).into(),
+            name_width: (name_chars as f32 + 2.0) * NAME_CHAR_WIDTH,
+            raw: rec.get_raw_line().into(),
+            log: log_lines.join("\n").into(),
+            log_lines: log_lines.len() as i32,
+            digsgn: if rec.get_digsgn().is_empty() { SharedString::new() } else { rec.get_digsgn_head().into() },
+        }
+    }).collect();
+    (summary, records)
+}
+
+/// 以目前的條件重新產生各來源的PKI記錄，以 CLI --pki 相同的方式寫入 (含 manifest 及 .rej)
+/// 回傳 (新增筆數, 剔除筆數)
+fn export_pki(path: &str, data: &mut ParsedData) -> io::Result<(usize, usize)> {
+    let mut writer = PkiWriter::open(path)?;
+    let mut added = 0;
+    let conditions = data.query.conditions.clone();
+    for (parser, source) in data.parsers.iter_mut().zip(&data.sources) {
+        let lines = pki_lines(parser, &conditions).map_err(io::Error::other)?;
+        writer.add_rejects(parser.take_pki_rejects(source));
+        added += writer.add_source(source, lines)?;
+    }
+    let rejects = writer.rejects().to_vec();
+    writer.finish()?;
+    let excluded = pki::write_rejects(&pki::rejects_path(path), &rejects)?;
+    Ok((added, excluded))
+}
+
+/// 將表格目前顯示的列 (依目前的排序) 寫成 CSV，回傳筆數
+fn export_csv(path: &str, headers: &[String], data: &ParsedData) -> io::Result<usize> {
+    let mut file = fs::File::create(path)?;
+    writeln!(file, "{}", headers.iter().map(|h| csv_field(h)).collect::<Vec<_>>().join(","))?;
+    for i in &data.visible {
+        writeln!(file, "{}", data.rows[*i].cells.iter().map(|c| csv_field(c)).collect::<Vec<_>>().join(","))?;
+    }
+    Ok(data.visible.len())
+}
+
+/// 將表格目前顯示的列所屬的委託寫成 JSON 陣列，同一委託只寫一次，回傳委託數
+fn export_json(path: &str, data: &ParsedData) -> io::Result<usize> {
+    let mut seen = BTreeSet::<(usize, String)>::new();
+    let mut orders = Vec::<serde_json::Value>::new();
+    for i in &data.visible {
+        let row = &data.rows[*i];
+        let parser = &data.parsers[row.parser_idx];
+        let list = order_list(parser, data.query.orders, &row.key);
+        let key = list.iter().find(|r| !r.is_req()).or(list.front()).map(|r| r.get_key().to_string()).unwrap_or_default();
+        if !seen.insert((row.parser_idx, key)) {
+            continue;
+        }
+        let mut order = parser.ord_rec.ord_list_to_json(&list);
+        order["source"] = serde_json::Value::String(data.sources[row.parser_idx].clone());
+        orders.push(order);
+    }
+    let file = fs::File::create(path)?;
+    serde_json::to_writer_pretty(file, &orders)?;
+    Ok(orders.len())
+}
+
+/// 開啟存檔對話框
+fn save_dialog(file_name: &str, filter: &str, ext: &str) -> Option<String> {
+    FileDialog::new()
+        .set_file_name(file_name)
+        .add_filter(filter, &[ext])
+        .save_file()
+        .map(|p| p.to_string_lossy().to_string())
+}
+
+fn table_model(rows: Vec<Vec<String>>) -> ModelRc<ModelRc<StandardListViewItem>> {
+    let rows: Vec<ModelRc<StandardListViewItem>> = rows.into_iter().map(|row| {
+        let cells: Vec<StandardListViewItem> = row.iter().map(|c| StandardListViewItem::from(c.as_str())).collect();
+        Rc::new(VecModel::from(cells)).into()
+    }).collect();
+    Rc::new(VecModel::from(rows)).into()
+}
+
+/// 來源的顯示名稱: 在日期目錄下的為目錄名，例如 /logs/20240102/SorReqOrd.log => 20240102，其他為檔名
+fn source_name(source: &str) -> String {
+    let path = Path::new(source);
+    let dir_name = path.parent().and_then(|d| d.file_name()).map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+    if dir_name.len() == 8 && dir_name.chars().all(|c| c.is_ascii_digit()) {
+        dir_name
+    } else {
+        path.file_name().map(|f| f.to_string_lossy().to_string()).unwrap_or_default()
+    }
+}
+
+fn format_secs(secs: i64) -> String {
+    Local.timestamp_opt(secs, 0).single().map(|t| t.format("%Y/%m/%d %H:%M:%S").to_string()).unwrap_or_default()
+}
+
+/// 合併多天的 (值, 筆數)，取筆數最多的前 TOP_N 個
+fn top_counts<I: Iterator<Item = Vec<(String, usize)>>>(lists: I) -> Vec<Vec<String>> {
+    let mut counts = BTreeMap::<String, usize>::new();
+    for (key, count) in lists.flatten() {
+        *counts.entry(key).or_default() += count;
+    }
+    let mut counts: Vec<(String, usize)> = counts.into_iter().collect();
+    counts.sort_by_key(|c| cmp::Reverse(c.1));
+    counts.into_iter().take(TOP_N).map(|(key, count)| vec![key, count.to_string()]).collect()
+}
+
+/// 每秒流量轉為長條，回傳 (長條, 起始時間, 結束時間)
+fn flow_bars(flow: &BTreeMap<i64, i32>) -> (Vec<Bar>, String, String) {
+    let (Some((first, _)), Some((last, _))) = (flow.first_key_value(), flow.last_key_value()) else {
+        return (Vec::new(), String::new(), String::new());
+    };
+    let width = (last - first) / MAX_FLOW_BARS + 1;
+    let mut peaks = vec![0; ((last - first) / width + 1) as usize];
+    for (secs, count) in flow {
+        let idx = ((secs - first) / width) as usize;
+        peaks[idx] = peaks[idx].max(*count);
+    }
+    let max = peaks.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bars = peaks.iter().enumerate().map(|(i, peak)| {
+        let start = first + i as i64 * width;
+        let text = if width == 1 {
+            format!("{}: {} req/s", format_secs(start), peak)
+        } else {
+            format!("{} - {}: peak {} req/s", format_secs(start), format_secs(start + width - 1), peak)
+        };
+        Bar { value: *peak as f32 / max, text: text.into() }
+    }).collect();
+    (bars, format_secs(*first), format_secs(*last))
+}
+
+/// 延遲分佈的長條及百分位數摘要
+fn latency_bars(mut latencies: Vec<f64>) -> (Vec<Bar>, String) {
+    if latencies.is_empty() {
+        return (Vec::new(), "no data".to_string());
+    }
+    latencies.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |q: f64| latencies[((latencies.len() - 1) as f64 * q).round() as usize];
+    let summary = format!("n={}, p50 {:.1}ms, p90 {:.1}ms, p99 {:.1}ms, max {:.1}ms",
+        latencies.len(), percentile(0.5), percentile(0.9), percentile(0.99), latencies[latencies.len() - 1]);
+    let mut counts = [0usize; LATENCY_BUCKETS.len()];
+    for ms in &latencies {
+        let idx = LATENCY_BUCKETS.iter().position(|(limit, _)| ms < limit).unwrap_or(LATENCY_BUCKETS.len() - 1);
+        counts[idx] += 1;
+    }
+    let max = counts.iter().copied().max().unwrap_or(0).max(1) as f32;
+    let bars = counts.iter().zip(LATENCY_BUCKETS.iter())
+        .map(|(count, (_, label))| Bar { value: *count as f32 / max, text: format!("{}: {} reqs", label, count).into() })
+        .collect();
+    (bars, summary)
+}
+
+/// 依選擇的日期 (或全部) 更新 Dashboard
+fn refresh_dashboard(ui: &AppWindow, data: &ParsedData) {
+    let mut days: Vec<SharedString> = vec!["All days".into()];
+    days.extend(data.sources.iter().map(|s| source_name(s).into()));
+    ui.set_dashboard_days(Rc::new(VecModel::from(days)).into());
+
+    let day_counts: Vec<Vec<String>> = data.parsers.iter().zip(&data.sources).map(|(parser, source)| {
+        let counts = parser.get_counts();
+        vec![source_name(source), counts.tables.to_string(), counts.reqs.to_string(), counts.ords.to_string(),
+            counts.deals.to_string(), counts.invalid.to_string()]
+    }).collect();
+    ui.set_day_counts(table_model(day_counts));
+
+    let selected: Vec<&Parser> = match usize::try_from(ui.get_dashboard_day()) {
+        Ok(day) if day > 0 => data.parsers.get(day - 1).into_iter().collect(),
+        _ => data.parsers.iter().collect(),
+    };
+    ui.set_failed_tables(table_model(top_counts(selected.iter().map(|p| p.count_failed_by_table()))));
+    ui.set_top_users(table_model(top_counts(selected.iter().map(|p| p.count_reqs_by("User")))));
+    ui.set_top_sessions(table_model(top_counts(selected.iter().map(|p| p.count_reqs_by("SesName")))));
+
+    let mut flow = BTreeMap::<i64, i32>::new();
+    for (secs, count) in selected.iter().flat_map(|p| p.req_flow()) {
+        *flow.entry(secs).or_default() += count;
+    }
+    let (bars, first, last) = flow_bars(&flow);
+    ui.set_flow_bars(Rc::new(VecModel::from(bars)).into());
+    ui.set_flow_first(first.into());
+    ui.set_flow_last(last.into());
+
+    let (bars, summary) = latency_bars(selected.iter().flat_map(|p| p.req_latencies()).collect());
+    ui.set_latency_bars(Rc::new(VecModel::from(bars)).into());
+    ui.set_latency_summary(summary.into());
+}
+
+/// 清除前一次的結果，開始在背景解析 logs
+fn start_parse(ui: &AppWindow, logs: Vec<PathBuf>, data: &Arc<Mutex<ParsedData>>, cancel: &Arc<AtomicBool>, layout: &PkiLayout) {
+    if ui.get_parsing() {
+        return;
+    }
+    if logs.is_empty() {
+        ui.set_status_text("No SorReqOrd.log found (in the directory or its YYYYMMDD sub-directories)".into());
+        return;
+    }
+    ui.set_status_text(format!("Found {} logs, parsing...", logs.len()).into());
+    clear_detail(ui);
+
+    // 列在解析過程中逐步加入
+    {
+        let mut data = lock_data(data);
+        let query = std::mem::take(&mut data.query);
+        *data = ParsedData { query, ..ParsedData::default() };
+        set_columns(ui, &mut data, layout);
+        set_table_names(ui, &data);
+        refresh_rows(ui, &mut data);
+    }
+
+    ui.set_dashboard_day(0);
+    ui.set_progress(0.0);
+    ui.set_parsing(true);
+    cancel.store(false, Ordering::Relaxed);
+    let ui_handle = ui.as_weak();
+    let encoding = ui.get_encoding().to_string();
+    let data = data.clone();
+    let cancel = cancel.clone();
+    let layout = layout.clone();
+    thread::spawn(move || parse_logs(ui_handle, logs, encoding, layout, data, cancel));
+}
+
+fn clear_detail(ui: &AppWindow) {
+    ui.set_selected_row(-1);
+    ui.set_detail_title("Select a row to show the order".into());
+    ui.set_detail_records(Rc::new(VecModel::from(Vec::<RecordDetail>::new())).into());
+}
+
 // GUI 的主函數
 pub fn run() {
     // 創建 AppWindow 元件的實例
     let ui = AppWindow::new().unwrap();
 
+    let layout = PkiLayout::default();
+    let data = Arc::new(Mutex::new(ParsedData::default()));
+    let cancel = Arc::new(AtomicBool::new(false));
+    let clipboard = Rc::new(RefCell::new(None::<Clipboard>));
+    let config = Rc::new(RefCell::new(GuiConfig::load()));
+    {
+        let config = config.borrow();
+        ui.set_encoding(config.encoding.as_str().into());
+        ui.set_view_mode(config.view_mode);
+        ui.set_conditions(config.conditions.as_str().into());
+    }
+    set_columns(&ui, &mut lock_data(&data), &layout);
+    ui.set_row_data(ModelRc::new(RowsModel { data: data.clone(), notify: ModelNotify::default() }));
+
     // 設定 open_dir_dialog 回呼的處理邏輯
     ui.on_open_dir_dialog({
         // 使用弱引用以避免循環引用
         let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let cancel = cancel.clone();
+        let layout = layout.clone();
+        let config = config.clone();
         move || {
             let ui = ui_handle.unwrap();
-            
+
             // 使用 rfd crate 來開啟系統的目錄選擇對話框
-            if let Some(dir_path) = FileDialog::new().pick_folder() {
-                ui.set_status_text("Scanning directories...".into());
-                
-                // 掃描所有日期格式的子目錄 (YYYYMMDD)
-                let date_regex = Regex::new(r"^\d{8}$").unwrap();
-                let mut date_dirs = Vec::new();
-                
-                if let Ok(entries) = fs::read_dir(&dir_path) {
-                    for entry in entries.flatten() {
-                        if let Ok(metadata) = entry.metadata() {
-                            if metadata.is_dir() {
-                                if let Some(dir_name) = entry.file_name().to_str() {
-                                    if date_regex.is_match(dir_name) {
-                                        date_dirs.push(entry.path());
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-                
-                date_dirs.sort();
-                
-                if date_dirs.is_empty() {
-                    ui.set_status_text("No date directories found (YYYYMMDD format)".into());
-                    ui.set_column_data(Rc::new(VecModel::from(Vec::<SharedString>::new())).into());
-                    ui.set_row_data(Rc::new(VecModel::from(Vec::<ModelRc<SharedString>>::new())).into());
-                    return;
+            if let Some(dir_path) = FileDialog::new().set_directory(&config.borrow().last_dir).pick_folder() {
+                config.borrow_mut().last_dir = dir_path.to_string_lossy().to_string();
+                start_parse(&ui, collect_logs(&dir_path), &data, &cancel, &layout);
+            }
+        }
+    });
+
+    ui.on_open_files_dialog({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let cancel = cancel.clone();
+        let layout = layout.clone();
+        let config = config.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            if let Some(files) = FileDialog::new().set_directory(&config.borrow().last_dir).pick_files() {
+                if let Some(dir) = files.first().and_then(|f| f.parent()) {
+                    config.borrow_mut().last_dir = dir.to_string_lossy().to_string();
                 }
-                
-                let status_msg = format!("Found {} date directories, parsing...", date_dirs.len());
-                ui.set_status_text(status_msg.into());
-                
-                let mut all_pki_lines = Vec::new();
-                let mut _total_parsed = 0;
-                
-                // 為每個日期目錄進行解析
-                for date_dir in &date_dirs {
-                    let req_log_path = date_dir.join("SorReqOrd.log");
-                    
-                    if req_log_path.exists() {
-                        if let Ok(f) = std::fs::File::open(&req_log_path) {
-                            let mut reader = BufReader::new(f);
-                            let mut parser = Parser::new();
-                            read_data_log(&mut reader, &mut parser, "BIG5");
-                            
-                            // 使用默認搜尋條件
-                            let default_conditions = "TwfNew:SesName:SorAPI|TwfChg:SesName:SorAPI|FrfNew:SesName:SorAPI|FrfChg:SesName:SorAPI";
-                            
-                            // 執行搜尋並輸出 PKI 格式
-                            parser.find_by_conditions(default_conditions, "", &false, true, true);
-                            
-                            // 獲取 PKI 輸出
-                            let pki_output = parser.get_pki_output();
-                            if !pki_output.is_empty() {
-                                all_pki_lines.extend(pki_output.lines().map(|s| s.to_string()));
-                                _total_parsed += 1;
-                            }
-                        }
-                    }
+                start_parse(&ui, files, &data, &cancel, &layout);
+            }
+        }
+    });
+
+    // 拖放到視窗的檔案或目錄: 一次拖放多個時會收到多個事件，稍候再一起解析
+    let dropped = Rc::new(RefCell::new(Vec::<PathBuf>::new()));
+    ui.window().on_winit_window_event({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let cancel = cancel.clone();
+        let layout = layout.clone();
+        move |_, event| {
+            if let WindowEvent::DroppedFile(path) = event {
+                let first = dropped.borrow().is_empty();
+                dropped.borrow_mut().push(path.clone());
+                if first {
+                    let ui_handle = ui_handle.clone();
+                    let dropped = dropped.clone();
+                    let data = data.clone();
+                    let cancel = cancel.clone();
+                    let layout = layout.clone();
+                    slint::Timer::single_shot(Duration::from_millis(200), move || {
+                        let paths = std::mem::take(&mut *dropped.borrow_mut());
+                        let logs: Vec<PathBuf> = paths.iter().flat_map(|p| collect_logs(p)).collect();
+                        start_parse(&ui_handle.unwrap(), logs, &data, &cancel, &layout);
+                    });
                 }
-                
-                // 顯示 PKI 輸出結果
-                if !all_pki_lines.is_empty() {
-                    let status_msg = format!("Parsed {} directories, {} PKI records", date_dirs.len(), all_pki_lines.len());
-                    ui.set_status_text(status_msg.into());
-                    
-                    // 設置列標題
-                    let headers = ["Date", "BrkNo", "Ivac", "Type", "FromUID", "Time", "DigsgnHash"];
-                    let column_data: Vec<SharedString> = headers.iter().map(|h| (*h).into()).collect();
-                    ui.set_column_data(Rc::new(VecModel::from(column_data)).into());
-                    
-                    // 解析 PKI 行並轉換為表格行
-                    let mut rows = Vec::new();
-                    for line in all_pki_lines {
-                        let parts: Vec<&str> = line.split('|').collect();
-                        if parts.len() >= 8 {
-                            let row: Vec<SharedString> = vec![
-                                parts[1].into(),  // Date
-                                parts[2].into(),  // BrkNo
-                                parts[3].into(),  // Ivac
-                                parts[4].into(),  // Type (O/C/M)
-                                parts[5].into(),  // FromUID
-                                parts[6].into(),  // Time
-                                parts[7].into(),  // Complete digsgn
-                            ];
-                            rows.push(Rc::new(VecModel::from(row)).into());
-                        }
-                    }
-                    
-                    ui.set_row_data(Rc::new(VecModel::from(rows)).into());
-                } else {
-                    ui.set_status_text("No PKI records found".into());
-                    ui.set_column_data(Rc::new(VecModel::from(Vec::<SharedString>::new())).into());
-                    ui.set_row_data(Rc::new(VecModel::from(Vec::<ModelRc<SharedString>>::new())).into());
+            }
+            WinitWindowEventResult::Propagate
+        }
+    });
+
+    ui.on_cancel_parse({
+        let ui_handle = ui.as_weak();
+        let cancel = cancel.clone();
+        move || {
+            cancel.store(true, Ordering::Relaxed);
+            ui_handle.unwrap().set_status_text("Cancelling...".into());
+        }
+    });
+
+    // 選擇表名後，列出該表的欄位
+    ui.on_table_picked({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move |table| {
+            let ui = ui_handle.unwrap();
+            let data = lock_data(&data);
+            let fields: Vec<SharedString> = data.tables.get(table.as_str())
+                .map(|fields| fields.iter().map(|f| f.as_str().into()).collect())
+                .unwrap_or_default();
+            ui.set_picked_field(fields.first().cloned().unwrap_or_default());
+            ui.set_field_names(Rc::new(VecModel::from(fields)).into());
+        }
+    });
+
+    // 將選擇的 表名:欄位名: 以 AND 接到條件之後
+    ui.on_insert_condition({
+        let ui_handle = ui.as_weak();
+        move || {
+            let ui = ui_handle.unwrap();
+            let mut conditions = ui.get_conditions().trim_end().to_string();
+            if !conditions.is_empty() && !conditions.ends_with([',', '|']) {
+                conditions.push(',');
+            }
+            conditions.push_str(&format!("{}:{}:", ui.get_picked_table(), ui.get_picked_field()));
+            ui.set_conditions(conditions.into());
+            ui.invoke_conditions_edited();
+        }
+    });
+
+    ui.on_conditions_edited({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let data = lock_data(&data);
+            let error = validate_conditions(&data.tables, &ui.get_conditions()).err().unwrap_or_default();
+            ui.set_condition_error(error.into());
+        }
+    });
+
+    // 以條件重新查詢所有已解析的 Parser
+    ui.on_run_search({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let layout = layout.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            if ui.get_parsing() {
+                return;
+            }
+            let mut data = lock_data(&data);
+            let query = Query { orders: ui.get_view_mode() == 1, conditions: ui.get_conditions().trim().to_string() };
+            if let Err(e) = validate_conditions(&data.tables, &query.conditions) {
+                ui.set_condition_error(e.into());
+                return;
+            }
+            let columns_changed = query.orders != data.query.orders;
+            let data = &mut *data;
+            data.query = query;
+            data.rows.clear();
+            let searched: Result<Vec<Vec<ResultRow>>, String> = data.parsers.iter_mut().enumerate()
+                .map(|(idx, parser)| query_rows(parser, idx, &data.query, &layout))
+                .collect();
+            match searched {
+                Ok(rows) => data.rows.extend(rows.into_iter().flatten()),
+                Err(e) => {
+                    refresh_rows(&ui, data);
+                    ui.set_status_text(format!("Search {} failed: {}", data.query.conditions, e).into());
+                    return;
                 }
             }
+            if columns_changed {
+                set_columns(&ui, data, &layout);
+            }
+            clear_detail(&ui);
+            refresh_rows(&ui, data);
+            let kind = if data.query.orders { "orders" } else { "PKI records" };
+            ui.set_status_text(format!("{} {} found", data.rows.len(), kind).into());
+        }
+    });
+
+    ui.on_filters_changed({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            clear_detail(&ui);
+            refresh_rows(&ui, &mut lock_data(&data));
+        }
+    });
+
+    ui.on_column_filter_edited({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move |column, text| {
+            let ui = ui_handle.unwrap();
+            let mut data = lock_data(&data);
+            if let Some(filter) = usize::try_from(column).ok().and_then(|c| data.column_filters.get_mut(c)) {
+                *filter = text.to_string();
+            }
+            clear_detail(&ui);
+            refresh_rows(&ui, &mut data);
+        }
+    });
+
+    // 點欄位標題時排序
+    ui.on_sort_rows({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move |column, ascending| {
+            let ui = ui_handle.unwrap();
+            let mut data = lock_data(&data);
+            data.sort = usize::try_from(column).ok().map(|c| (c, ascending));
+            clear_detail(&ui);
+            refresh_rows(&ui, &mut data);
+        }
+    });
+
+    ui.on_copy_cell({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let clipboard = clipboard.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let cell = selected_cells(&ui, &lock_data(&data))
+                .and_then(|cells| usize::try_from(ui.get_copy_column()).ok().and_then(|c| cells.get(c).cloned()));
+            if let Some(cell) = cell {
+                copy_to_clipboard(&ui, &clipboard, cell, "cell");
+            }
+        }
+    });
+
+    // 整列以 tab 分隔複製，可直接貼到試算表
+    ui.on_copy_row({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        let clipboard = clipboard.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let cells = selected_cells(&ui, &lock_data(&data));
+            if let Some(cells) = cells {
+                copy_to_clipboard(&ui, &clipboard, cells.join("\t"), "row");
+            }
+        }
+    });
+
+    ui.on_export_pki({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let Some(path) = save_dialog(&pki::default_output_file(), "PKI log", "log") else {
+                return;
+            };
+            let text = match export_pki(&path, &mut lock_data(&data)) {
+                Ok((added, 0)) => format!("{} new PKI records saved to {}", added, path),
+                Ok((added, excluded)) => format!("{} new PKI records saved to {}, {} excluded, see {}", added, path, excluded, pki::rejects_path(&path)),
+                Err(e) => format!("Error writing {}: {}", path, e),
+            };
+            ui.set_status_text(text.into());
+        }
+    });
+
+    ui.on_export_csv({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let Some(path) = save_dialog("sor_logparser.csv", "CSV", "csv") else {
+                return;
+            };
+            let headers: Vec<String> = ui.get_column_data().iter().map(|h| h.to_string()).collect();
+            let text = match export_csv(&path, &headers, &lock_data(&data)) {
+                Ok(rows) => format!("{} rows saved to {}", rows, path),
+                Err(e) => format!("Error writing {}: {}", path, e),
+            };
+            ui.set_status_text(text.into());
+        }
+    });
+
+    ui.on_export_json({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            let Some(path) = save_dialog("sor_orders.json", "JSON", "json") else {
+                return;
+            };
+            let text = match export_json(&path, &lock_data(&data)) {
+                Ok(orders) => format!("{} orders saved to {}", orders, path),
+                Err(e) => format!("Error writing {}: {}", path, e),
+            };
+            ui.set_status_text(text.into());
+        }
+    });
+
+    ui.on_dashboard_day_changed({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move || {
+            let ui = ui_handle.unwrap();
+            if !ui.get_parsing() {
+                refresh_dashboard(&ui, &lock_data(&data));
+            }
+        }
+    });
+
+    // 選取一列時，顯示該委託的完整歷程
+    ui.on_row_selected({
+        let ui_handle = ui.as_weak();
+        let data = data.clone();
+        move |row| {
+            let ui = ui_handle.unwrap();
+            let data = lock_data(&data);
+            if let Some(row) = usize::try_from(row).ok().and_then(|r| data.visible.get(r)).map(|i| &data.rows[*i]) {
+                let (summary, records) = order_details(&data.parsers[row.parser_idx], data.query.orders, &row.key, &data.query.conditions);
+                ui.set_detail_title(summary.into());
+                ui.set_detail_records(Rc::new(VecModel::from(records)).into());
+            }
         }
     });
 
-    // 運行 GUI 事件循環
+    // 運行 GUI 事件循環，視窗關閉時停止仍在進行的解析並保存設定
     ui.run().unwrap();
+    cancel.store(true, Ordering::Relaxed);
+    let mut config = config.borrow_mut();
+    config.encoding = ui.get_encoding().to_string();
+    config.view_mode = ui.get_view_mode();
+    config.conditions = ui.get_conditions().to_string();
+    config.save();
 }
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use regex::Regex;
+
+use crate::parser::Rec;
+
+// 記錄附加 log 的反向索引: 詞 -> 含有該詞的記錄
+// 全文搜尋時只需比對同時含有所有詞的候選記錄，不必逐筆掃描
+
+/// 英數字及 _ 連續時為一個詞
+fn is_word_char(c: char) -> bool {
+	c.is_ascii_alphanumeric() || c == '_'
+}
+
+/// 將文字切成詞: 連續的英數字為一個詞 (轉小寫)，其他文字 (例如中文) 每個字為一個詞
+pub fn tokenize(text: &str) -> Vec<String> {
+	let mut words = Vec::new();
+	let mut word = String::new();
+	for c in text.chars() {
+		if is_word_char(c) {
+			word.push(c.to_ascii_lowercase());
+			continue;
+		}
+		if !word.is_empty() {
+			words.push(std::mem::take(&mut word));
+		}
+		if c.is_alphanumeric() {
+			words.extend(c.to_lowercase().map(String::from));
+		}
+	}
+	if !word.is_empty() {
+		words.push(word);
+	}
+	words
+}
+
+/// 搜尋時比對的 log 內容: 欄位分隔字元 \x01 以 | 表示，與畫面上顯示的相同
+fn log_text(rec: &Rec) -> String {
+	rec.get_log().replace('\x01', "|")
+}
+
+#[derive(Default)]
+pub struct LogIndex {
+	recs : Vec<Arc<Rec>>,           // 有附加 log 的記錄
+	words: HashMap<String, Vec<u32>>, // 詞 - 含有該詞的記錄 (recs 的 index, 遞增)
+}
+
+impl LogIndex {
+	pub fn build<'a, I: Iterator<Item = &'a Arc<Rec>>>(recs: I) -> LogIndex {
+		let mut index = LogIndex::default();
+		for rec in recs.filter(|rec| !rec.get_log().is_empty()) {
+			let idx = index.recs.len() as u32;
+			for word in tokenize(rec.get_log()) {
+				let postings = index.words.entry(word).or_default();
+				if postings.last() != Some(&idx) {
+					postings.push(idx);
+				}
+			}
+			index.recs.push(Arc::clone(rec));
+		}
+		index
+	}
+	/// 含有符合 matches 的詞的記錄 (遞增)
+	fn postings_where<F: Fn(&str) -> bool>(&self, matches: F) -> Vec<u32> {
+		let mut ret: Vec<u32> = self.words.iter()
+			.filter(|(word, _)| matches(word))
+			.flat_map(|(_, postings)| postings.iter().cloned())
+			.collect();
+		ret.sort_unstable();
+		ret.dedup();
+		ret
+	}
+	/// 全文搜尋 (不分大小寫): log 中含有整串 text 的記錄
+	/// text 可以從詞的中間開始或結束: 開頭的詞只需是 log 中某個詞的結尾，最後的詞只需是某個詞的開頭，
+	/// 只有一個詞時可以是任一詞的一部份；中間的詞必須完整
+	/// 先以索引找出含有這些詞的候選記錄，再比對整串
+	pub fn find_text(&self, text: &str) -> Vec<&Arc<Rec>> {
+		let target = text.trim().to_lowercase();
+		if target.is_empty() {
+			return Vec::new();
+		}
+		let words = tokenize(&target);
+		let open_start = target.starts_with(is_word_char);
+		let open_end = target.ends_with(is_word_char);
+		let candidates: Vec<u32> = if words.is_empty() {
+			(0..self.recs.len() as u32).collect()
+		} else {
+			let last = words.len() - 1;
+			let mut postings = Vec::<Vec<u32>>::new();
+			for (i, word) in words.iter().enumerate() {
+				let word = word.as_str();
+				let p = match (i == 0 && open_start, i == last && open_end) {
+					(true, true)   => self.postings_where(|w| w.contains(word)),
+					(true, false)  => self.postings_where(|w| w.ends_with(word)),
+					(false, true)  => self.postings_where(|w| w.starts_with(word)),
+					(false, false) => self.words.get(word).cloned().unwrap_or_default(),
+				};
+				if p.is_empty() {
+					return Vec::new();
+				}
+				postings.push(p);
+			}
+			postings.sort_by_key(|p| p.len());
+			postings[0].iter()
+				.filter(|idx| postings[1..].iter().all(|p| p.binary_search(idx).is_ok()))
+				.cloned()
+				.collect()
+		};
+		candidates.into_iter()
+			.map(|idx| &self.recs[idx as usize])
+			.filter(|rec| log_text(rec).to_lowercase().contains(&target))
+			.collect()
+	}
+	/// log 符合正規表示式的記錄
+	pub fn find_regex(&self, re: &Regex) -> Vec<&Arc<Rec>> {
+		self.recs.iter().filter(|rec| re.is_match(&log_text(rec))).collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parser::Parser;
+
+	#[test]
+	fn tokenize_splits_words_and_cjk() {
+		assert_eq!(tokenize("Err=Insufficient margin|SIGA"), ["err", "insufficient", "margin", "siga"]);
+		assert_eq!(tokenize("保證金 不足 ord_no=A01"), ["保", "證", "金", "不", "足", "ord_no", "a01"]);
+		assert!(tokenize(" |=, ").is_empty());
+	}
+
+	/// 每筆 Req 附加一行 log
+	fn index_of(logs: &[&str]) -> Parser {
+		let mut parser = Parser::new();
+		parser.parse_line("Req\x01-\x01TwfNew\x01Time\x01ReqKind", "", "", 1);
+		for (idx, log) in logs.iter().enumerate() {
+			let line = format!("Req\x01R{}\x01TwfNew\x011704157200.{:06}\x011", idx, idx);
+			parser.parse_line(&line, &log.replace('|', "\x01"), "", idx + 2);
+		}
+		parser
+	}
+
+	fn found_keys(parser: &Parser, text: &str) -> Vec<String> {
+		let mut keys: Vec<String> = parser.ord_rec.log_index().find_text(text).iter().map(|rec| rec.get_key().to_string()).collect();
+		keys.sort();
+		keys
+	}
+
+	#[test]
+	fn find_text_matches_substrings() {
+		let parser = index_of(&[
+			":Err=Insufficient margin|SIGNATURE1",
+			":Err=Bad price|SIGNATURE2",
+			":Msg=保證金不足|SIGNATURE3",
+		]);
+		assert_eq!(found_keys(&parser, "margin"), ["R0"]);
+		assert_eq!(found_keys(&parser, "marg"), ["R0"]);
+		assert_eq!(found_keys(&parser, "ufficient margin"), ["R0"]);
+		assert_eq!(found_keys(&parser, "ufficient marg"), ["R0"]);
+		assert_eq!(found_keys(&parser, "SIGNAT"), ["R0", "R1", "R2"]);
+		assert_eq!(found_keys(&parser, "ignature2"), ["R1"]);
+		assert_eq!(found_keys(&parser, "err=bad"), ["R1"]);
+		assert_eq!(found_keys(&parser, "保證金"), ["R2"]);
+		assert_eq!(found_keys(&parser, "margin|sig"), ["R0"]);
+		assert_eq!(found_keys(&parser, "=Insufficient|"), Vec::<String>::new());
+		assert_eq!(found_keys(&parser, "margin price"), Vec::<String>::new());
+		assert_eq!(found_keys(&parser, "nothing"), Vec::<String>::new());
+	}
+}
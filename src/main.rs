@@ -1,9 +1,7 @@
 use structopt::StructOpt;
 use std::io::*;
-use std::io::{BufReader, Write};
-use std::fs::{File, self, OpenOptions};
+use std::fs;
 use std::path::Path;
-use chrono::Local;
 
 mod parser;
 use crate::parser::*;
@@ -11,7 +9,27 @@ use crate::parser::*;
 mod fileread;
 use crate::fileread::*;
 
+mod pki;
+use crate::pki::{PkiLayout, PkiLine, PkiReject, PkiWriter};
+use crate::codes::CodeBook;
+
+/// PKI 模式下有記錄被剔除或標記時的結束代碼
+const PKI_REJECT_EXIT_CODE: i32 = 2;
+
+mod check;
+mod codes;
+mod fills;
+mod gaps;
 pub mod gui;
+mod logindex;
+mod pki_query;
+mod positions;
+mod rejects;
+mod repl;
+mod serve;
+mod snapshot;
+mod tui;
+mod util;
 
 /// SorReqOrd Parser
 /// Retrieve record of specified fields from given SorReqOrd.log
@@ -36,6 +54,36 @@ struct Options {
 	/// path of the saving file
 	#[structopt(short="o", long="output", default_value = "")]
 	savepath: String,
+	/// search the log lines attached to the records for this text (case-insensitive)
+	#[structopt(long="text", conflicts_with = "regex")]
+	text: Option<String>,
+	/// search the log lines attached to the records with a regular expression
+	#[structopt(long="regex")]
+	regex: Option<String>,
+	/// list the tags of the Tag=Value fields in the ':' log lines, with the number of records having each; lists each date directory when no file is given
+	#[structopt(long="log-tags")]
+	log_tags: bool,
+	/// per-order filled quantity and average price from the deal records, flagging overfills and fills after cancel; reports each date directory when no file is given
+	#[structopt(long="fills")]
+	fills: bool,
+	/// bought/sold quantity, net position, turnover and order count per account and symbol; scans the date directories when no file is given
+	#[structopt(long="positions")]
+	positions: bool,
+	/// check for data-quality anomalies: duplicate SorRID, overwritten Req keys, timestamps going backwards, missing fields, unknown tables, bad timestamps; checks each date directory when no file is given
+	#[structopt(long="check")]
+	check: bool,
+	/// list the rejected requests (OrderSt or ReqStep 99) with their user, kind and reject text, grouped by reason; lists each date directory when no file is given
+	#[structopt(long="rejects")]
+	rejects: bool,
+	/// list the gaps between records longer than --gap-secs in trading hours, with the bursts after them and the orders in flight; checks each date directory when no file is given
+	#[structopt(long="gaps")]
+	gaps: bool,
+	/// minimal gap in seconds for --gaps
+	#[structopt(long="gap-secs")]
+	gap_secs: Option<f64>,
+	/// trading hours for --gaps, as HH:MM-HH:MM joined by ","; a range ending before its start runs past midnight
+	#[structopt(long="trading-hours", default_value = gaps::DEFAULT_TRADING_HOURS)]
+	trading_hours: String,
 	/// statistic a field's values
 	#[structopt(short="t", long="statistic", default_value = "")]
 	table_field: String,
@@ -51,6 +99,48 @@ struct Options {
 	/// output to PKILog-{date}.log file
 	#[structopt(long="pki")]
 	pki_output: bool,
+	/// TOML file with the labels of the ReqKind/OrderSt/ReqStep codes (default: built-in codes.toml)
+	#[structopt(long="codes", default_value = "")]
+	codes: String,
+	/// language of the code labels: zh or en (default: the code file's lang)
+	#[structopt(long="lang", default_value = "")]
+	lang: String,
+	/// TOML file describing the PKI record layout (default: built-in layout)
+	#[structopt(long="pki-layout", default_value = "")]
+	pki_layout: String,
+	/// add header/trailer records (counts and SHA-256) to the PKI file
+	#[structopt(long="pki-envelope")]
+	pki_envelope: bool,
+	/// verify a PKI file's trailer against its content
+	#[structopt(long="pki-verify", default_value = "")]
+	pki_verify: String,
+	/// load existing PKILog files (repeatable) and show matching records with counts
+	#[structopt(long="pki-query", number_of_values = 1)]
+	pki_query: Vec<String>,
+	/// filter for --pki-query: FieldName:Value joined by ","; ex: --pki-filter Date:20240102,Type:O
+	#[structopt(long="pki-filter", default_value = "")]
+	pki_filter: String,
+	/// cross-check --pki-query records against the SorReqOrd.log files that produced them
+	#[structopt(long="pki-check")]
+	pki_check: bool,
+	/// parse the log once, then query it interactively
+	#[structopt(long="repl")]
+	repl: bool,
+	/// parse the log once, then browse orders in a terminal UI
+	#[structopt(long="tui")]
+	tui: bool,
+	/// parse the log (or the date directories) once, then serve a local HTTP JSON API
+	#[structopt(long="serve")]
+	serve: bool,
+	/// address for --serve
+	#[structopt(long="listen", default_value = "127.0.0.1:8080")]
+	listen: String,
+	/// always parse the log instead of loading or saving its snapshot
+	#[structopt(long="no-cache")]
+	no_cache: bool,
+	/// print found records as aligned field=value pairs, marking the fields matched by -f
+	#[structopt(short="p", long="pretty")]
+	pretty: bool,
 }
 
 /// 檢查目錄名是否為日期格式 (8位數字)
@@ -81,27 +171,94 @@ fn find_date_directories(dir_path: &str) -> Result<Vec<String>> {
 	Ok(date_dirs)
 }
 
+/// 取得指定目錄下各日期目錄中的 SorReqOrd.log
+fn find_date_logs(dir_path: &str) -> Result<Vec<String>> {
+	Ok(find_date_directories(dir_path)?.iter()
+		.map(|dir| Path::new(dir).join("SorReqOrd.log"))
+		.filter(|path| path.exists())
+		.map(|path| path.to_string_lossy().to_string())
+		.collect())
+}
+
+/// 是否指定了對單一 log 的報表，未指定檔案時對各日期目錄分別產生
+fn has_reports(options: &Options) -> bool {
+	options.log_tags || options.fills || options.check || options.rejects || options.gaps
+}
+
+/// 印出單一 log 的報表
+fn print_reports(parser: &Parser, options: &Options) {
+	// 列出 log 欄位
+	if options.log_tags {
+		print!("{}", parser.log_tags_statistic());
+	}
+
+	// 成交統計
+	if options.fills {
+		print!("{}", fills::format_report(&parser.ord_rec, false));
+	}
+
+	// 資料品質檢查
+	if options.check {
+		print!("{}", check::format_report(&parser.ord_rec));
+	}
+
+	// 失敗委託
+	if options.rejects {
+		print!("{}", rejects::format_report(&parser.ord_rec));
+	}
+
+	// 記錄中斷檢查
+	if options.gaps {
+		let gap_secs = options.gap_secs.unwrap_or(gaps::DEFAULT_GAP_SECS);
+		match gaps::TradingHours::parse(&options.trading_hours) {
+			Ok(hours) => print!("{}", gaps::format_report(&gaps::find_gaps(&parser.ord_rec, gap_secs, &hours), gap_secs)),
+			Err(e) => println!("{}", e),
+		}
+	}
+}
+
+/// 逐一解析各日期目錄的 SorReqOrd.log 並印出報表
+fn scan_reports(options: &Options) {
+	let logs = match find_date_logs(&options.scan_dir) {
+		Ok(logs) => logs,
+		Err(e) => {
+			println!("Error reading directory {}: {}", options.scan_dir, e);
+			return;
+		}
+	};
+	if logs.is_empty() {
+		println!("No SorReqOrd.log files found in date directories");
+		return;
+	}
+	for log in &logs {
+		println!("=== {} ===", log);
+		let mut parser = Parser::new();
+		match read_log_file(Path::new(log), &mut parser, &options.encoding) {
+			Ok(()) => print_reports(&parser, options),
+			Err(e) => println!("error opening {}: {}", log, e),
+		}
+	}
+}
+
 /// 處理單個SorReqOrd.log檔案
-fn process_log_file(filepath: &str, encoding: &str, pki_mode: bool, search_field: &str) -> Result<String> {
+/// pki: PKI模式時，產生的記錄經由此 writer 去重後附加到PKI檔
+fn process_log_file(filepath: &str, encoding: &str, search_field: &str, layout: &PkiLayout, pki: Option<&mut PkiWriter>) -> Result<String> {
 	let mut output = String::new();
 	
-	if let Ok(f) = File::open(filepath) {
-		let mut reader = BufReader::new(f);
-		let mut parser = Parser::new();
-		
-		read_data_log(&mut reader, &mut parser, encoding);
-		
-		if pki_mode {
+	let mut parser = Parser::new();
+	parser.set_pki_layout(layout.clone());
+	if read_log_file(Path::new(filepath), &mut parser, encoding).is_ok() {
+		if let Some(writer) = pki {
 			// PKI 模式：執行搜尋或輸出所有記錄
-			if !search_field.is_empty() {
-				// 執行搜尋，find_by_conditions 會自動輸出到檔案
-				parser.find_by_conditions(search_field, "", &true, true, true);
-				// 搜尋已由 find_by_conditions 處理，此處返回空
-				return Ok(String::new());
+			let lines = if !search_field.is_empty() {
+				parser.find_by_conditions(search_field, "", &true, true, true).map_err(Error::other)?;
+				parser.take_pki_lines()
 			} else {
-				// 沒有搜尋條件，返回所有記錄的 PKI 格式供上層累積
-				output = parser.get_pki_output();
-			}
+				parser.get_pki_records()
+			};
+			writer.add_rejects(parser.take_pki_rejects(filepath));
+			let added = writer.add_source(filepath, lines)?;
+			println!("{} new PKI records", added);
 			// Parser 會在此方法結束後自動釋放，每個檔案都用新的 Parser
 		} else {
 			// 普通模式：輸出詳細資訊
@@ -127,37 +284,33 @@ fn process_log_file(filepath: &str, encoding: &str, pki_mode: bool, search_field
 }
 
 /// 掃描日期目錄並解析所有SorReqOrd.log
-fn scan_and_parse_date_dirs(base_dir: &str, encoding: &str, use_pki: bool, search_field: &str) -> Result<()> {
+/// 回傳PKI模式下被剔除及標記的記錄筆數
+/// envelope: PKI檔是否加上表頭與表尾
+fn scan_and_parse_date_dirs(base_dir: &str, encoding: &str, use_pki: bool, search_field: &str, layout: &PkiLayout, envelope: bool) -> Result<usize> {
 	let date_dirs = match find_date_directories(base_dir) {
 		Ok(dirs) => dirs,
 		Err(e) => {
 			println!("Error reading directory {}: {}", base_dir, e);
-			return Ok(());
+			return Ok(0);
 		}
 	};
 	
 	if date_dirs.is_empty() {
 		println!("No date-named directories found in {}", base_dir);
-		return Ok(());
+		return Ok(0);
 	}
 	
-	let mut pki_file = if use_pki {
-		let now = Local::now();
-		let date_str = now.format("%Y%m%d").to_string();
-		let output_file = format!("PKILog-{}.log", date_str);
-		// 清空前次執行的內容
-		let _ = fs::write(&output_file, "");
-		match OpenOptions::new()
-			.create(true)
-			.append(true)
-			.open(&output_file) {
-			Ok(file) => {
-				println!("Creating PKI output file: {}", output_file);
-				Some(file)
+	// 只附加新的記錄，已寫入過的記錄由 manifest 判斷略過
+	let mut pki_writer = if use_pki {
+		let output_file = pki::default_output_file();
+		match PkiWriter::open(&output_file) {
+			Ok(writer) => {
+				println!("PKI output file: {}", output_file);
+				Some(writer)
 			},
 			Err(e) => {
-				println!("Error creating {}: {}", output_file, e);
-				None
+				println!("Error opening {}: {}", output_file, e);
+				return Ok(0);
 			}
 		}
 	} else {
@@ -173,82 +326,231 @@ fn scan_and_parse_date_dirs(base_dir: &str, encoding: &str, use_pki: bool, searc
 		if log_path.exists() {
 			found_logs = true;
 			println!("Processing: {}", log_file);
-			match process_log_file(&log_file, encoding, use_pki, search_field) {
-				Ok(output) => {
-					if use_pki {
-						// 直接寫入檔案，然後釋放記憶體
-						if let Some(ref mut file) = pki_file {
-							let _ = file.write_all(output.as_bytes());
-						}
-					} else {
-						print!("{}", output);
-					}
-				},
-				Err(e) => {
-					let err_msg = format!("Error processing {}: {}\n\n", log_file, e);
-					if use_pki {
-						if let Some(ref mut file) = pki_file {
-							let _ = file.write_all(err_msg.as_bytes());
-						}
-					} else {
-						print!("{}", err_msg);
-					}
-				}
+			match process_log_file(&log_file, encoding, search_field, layout, pki_writer.as_mut()) {
+				Ok(output) => print!("{}", output),
+				Err(e) => print!("Error processing {}: {}\n\n", log_file, e),
 			}
 		}
 	}
 	
 	if !found_logs {
 		println!("No SorReqOrd.log files found in date directories");
-		return Ok(());
+		return Ok(0);
 	}
 	
 	// PKI 模式的檔案已在迴圈中逐個寫入
-	if use_pki
-		&& pki_file.is_some() {
-			let now = Local::now();
-			let date_str = now.format("%Y%m%d").to_string();
-			let output_file = format!("PKILog-{}.log", date_str);
-			println!("PKI output saved to: {}", output_file);
+	match pki_writer {
+		Some(writer) => Ok(finish_pki_output(writer, layout, envelope)),
+		None => Ok(0),
+	}
+}
+
+/// 將單一來源檔產生的PKI記錄附加到 output_file，回傳被剔除及標記的筆數
+fn save_pki_output(output_file: &str, source: &str, lines: Vec<PkiLine>, rejects: Vec<PkiReject>, layout: &PkiLayout, envelope: bool) -> usize {
+	match PkiWriter::open(output_file) {
+		Ok(mut writer) => {
+			writer.add_rejects(rejects);
+			match writer.add_source(source, lines) {
+				Ok(added) => println!("{} new PKI records", added),
+				Err(e) => println!("Error writing {}: {}", output_file, e),
+			}
+			finish_pki_output(writer, layout, envelope)
+		},
+		Err(e) => {
+			println!("Error opening {}: {}", output_file, e);
+			0
 		}
-	
-	Ok(())
+	}
+}
+
+/// 寫回 manifest、表頭表尾及剔除記錄，回傳被剔除及標記的筆數
+/// 輸出檔原本已有表頭表尾時，即使未指定 envelope 也會重寫，讓新附加的記錄留在表尾之前
+fn finish_pki_output(writer: PkiWriter, layout: &PkiLayout, envelope: bool) -> usize {
+	let output_file = writer.output().to_string();
+	let rejects = writer.rejects().to_vec();
+	let envelope = envelope || writer.has_envelope();
+	match writer.finish() {
+		Ok(sources) => {
+			if envelope && Path::new(&output_file).exists() {
+				if let Err(e) = pki::write_envelope(&output_file, layout, &sources) {
+					println!("Error writing header/trailer of {}: {}", output_file, e);
+				}
+			}
+		},
+		Err(e) => println!("Error writing manifest of {}: {}", output_file, e),
+	}
+	println!("PKI output saved to: {}", output_file);
+	save_pki_rejects(&output_file, &rejects)
+}
+
+/// 檢查PKI檔的表尾，回傳是否通過
+fn verify_pki_file(path: &str, layout: &PkiLayout) -> bool {
+	match pki::verify(path, layout) {
+		Ok(problems) => {
+			if problems.is_empty() {
+				println!("{}: OK", path);
+				return true;
+			}
+			println!("{}: FAILED", path);
+			for problem in problems {
+				println!("  {}", problem);
+			}
+			false
+		},
+		Err(e) => {
+			println!("error opening {}: {}", path, e);
+			false
+		}
+	}
+}
+
+/// 將PKI剔除記錄寫入 output_file 對應的 .rej 檔，回傳被剔除及標記的筆數
+/// 標記的記錄雖已寫入PKI檔，仍需人工確認
+fn save_pki_rejects(output_file: &str, rejects: &[PkiReject]) -> usize {
+	let rej_file = pki::rejects_path(output_file);
+	match pki::write_rejects(&rej_file, rejects) {
+		Ok(excluded) => {
+			if !rejects.is_empty() {
+				println!("{} PKI records excluded, {} flagged, see {}", excluded, rejects.len() - excluded, rej_file);
+			}
+			rejects.len()
+		},
+		Err(e) => {
+			println!("Error writing {}: {}", rej_file, e);
+			rejects.len()
+		}
+	}
 }
 
 /// 第一參數指定檔案
 /// 將其讀入陣列以便解析
 fn main() -> Result<()> {
 	let mut options = Options::from_args();
+	snapshot::set_enabled(!options.no_cache);
+
+	// 載入代碼對照表，未指定時使用內建的
+	let code_book = if options.codes.is_empty() {
+		CodeBook::builtin()
+	} else {
+		match CodeBook::load(&options.codes) {
+			Ok(book) => book,
+			Err(e) => {
+				println!("error loading code table {}", e);
+				return Ok(());
+			}
+		}
+	};
+	codes::install(code_book, &options.lang);
 
     if options.gui {
         gui::run();
         return Ok(());
     }
 
+	if (options.repl || options.tui) && options.filepath.is_none() {
+		println!("please specify the SorReqOrd.log for --repl or --tui");
+		return Ok(());
+	}
+
+	if (options.text.is_some() || options.regex.is_some()) && options.filepath.is_none() {
+		println!("please specify the SorReqOrd.log for --text or --regex");
+		return Ok(());
+	}
+
 	// 若沒有任何輸入參數，設定預設值：目錄掃描 + 搜尋條件 + PKI 輸出
-	if options.filepath.is_none() && options.field.is_empty() && !options.pki_output && !options.save && !options.show_flow && options.table_field.is_empty() {
+	if options.filepath.is_none() && options.field.is_empty() && !options.pki_output && !options.save && !options.show_flow && options.table_field.is_empty()
+		&& options.text.is_none() && options.regex.is_none() && !options.log_tags && !options.fills && !options.positions && !options.check && !options.gaps && !options.rejects {
 		// 設定預設值
 		options.field = "TwfNew:SesName:SorAPI|TwfChg:SesName:SorAPI|FrfNew:SesName:SorAPI|FrfChg:SesName:SorAPI".to_string();
 		options.pki_output = true;
 	}
 
+	// 載入PKI版面設定，未指定時使用內建格式
+	let layout = if options.pki_layout.is_empty() {
+		PkiLayout::default()
+	} else {
+		match PkiLayout::load(&options.pki_layout) {
+			Ok(layout) => layout,
+			Err(e) => {
+				println!("error loading PKI layout {}", e);
+				return Ok(());
+			}
+		}
+	};
+
+	// 檢查既有的PKI檔
+	if !options.pki_verify.is_empty() {
+		if !verify_pki_file(&options.pki_verify, &layout) {
+			std::process::exit(1);
+		}
+		return Ok(());
+	}
+
+	// 查詢既有的PKI檔
+	if !options.pki_query.is_empty() {
+		if !pki_query::run(&options.pki_query, &options.pki_filter, &layout, options.hide, options.pki_check, &options.scan_dir, &options.encoding, &options.field) {
+			std::process::exit(1);
+		}
+		return Ok(());
+	}
+
+	// HTTP API 模式
+	if options.serve {
+		return serve::run(options.filepath.as_deref(), &options.scan_dir, &options.encoding, &layout, &options.listen);
+	}
+
+	// 帳戶部位報表，未指定檔案時統計各日期目錄
+	if options.positions {
+		positions::run(options.filepath.as_deref(), &options.scan_dir, &options.encoding, &options.savepath);
+		return Ok(());
+	}
+
+	// 交易時段有誤時不必解析 log
+	if options.gaps {
+		if let Err(e) = gaps::TradingHours::parse(&options.trading_hours) {
+			println!("{}", e);
+			return Ok(());
+		}
+	}
+
+	// 報表未指定檔案時，對各日期目錄分別產生
+	if options.filepath.is_none() && has_reports(&options) {
+		scan_reports(&options);
+		return Ok(());
+	}
+
 	// 若未指定檔案參數，則掃描日期目錄
 	if options.filepath.is_none() {
-		return scan_and_parse_date_dirs(&options.scan_dir, &options.encoding, options.pki_output, &options.field);
+		let reported = scan_and_parse_date_dirs(&options.scan_dir, &options.encoding, options.pki_output, &options.field, &layout, options.pki_envelope)?;
+		if reported > 0 {
+			std::process::exit(PKI_REJECT_EXIT_CODE);
+		}
+		return Ok(());
 	}
 
 	// 解析SorReqOrd.log
-	if let Some(filepath) = options.filepath {
-		if let Ok(f) = File::open(&filepath) {
-			let mut reader = BufReader::new(f);
-			let mut parser = Parser::new();
+	if let Some(filepath) = options.filepath.clone() {
+		let mut parser = Parser::new();
+		parser.set_pki_layout(layout.clone());
+		parser.set_pretty(options.pretty);
 
-			// 依每行解析
-			read_data_log(&mut reader, &mut parser, &options.encoding);
+		// 依每行解析，有快照時直接載入
+		if read_log_file(Path::new(&filepath), &mut parser, &options.encoding).is_ok() {
 
 			// 解析完了, 顯示解析結果
 			println!("-=summary=-\n{}", parser.get_info());
 
+			// 終端機介面
+			if options.tui {
+				return tui::run(&mut parser);
+			}
+
+			// 互動模式
+			if options.repl {
+				repl::run(parser);
+				return Ok(());
+			}
+
 			let unlinkreqs_info = parser.list_unlink_req();
 			if !unlinkreqs_info.is_empty() {
 				println!("there are unlink reqs:\n{}", unlinkreqs_info);
@@ -262,29 +564,54 @@ fn main() -> Result<()> {
 						tmp.push_str(".log");
 						tmp
 					} else {
-						options.savepath
+						options.savepath.clone()
 					}
 				} else {
 					"".to_string()
 				};
-				parser.find_by_conditions(&options.field, &savepath, &options.hide, options.pki_output, false);
+				if let Err(e) = parser.find_by_conditions(&options.field, &savepath, &options.hide, options.pki_output, false) {
+					println!("{}", e);
+					return Ok(());
+				}
+				if options.pki_output {
+					let output_file = if savepath.is_empty() { pki::default_output_file() } else { savepath };
+					let lines = parser.take_pki_lines();
+					let rejects = parser.take_pki_rejects(&filepath);
+					if save_pki_output(&output_file, &filepath, lines, rejects, &layout, options.pki_envelope) > 0 {
+						std::process::exit(PKI_REJECT_EXIT_CODE);
+					}
+				}
+			}
+
+			// 在附加的 log 中搜尋
+			// --text 與 --regex 不能同時指定
+			let found = match (&options.text, &options.regex) {
+				(Some(text), _) => Some(Ok(parser.search_log_text(text))),
+				(None, Some(regex)) => Some(parser.search_log_regex(regex).map_err(|e| format!("invalid regex {}: {}", regex, e))),
+				(None, None) => None,
+			};
+			if let Some(found) = found {
+				match found {
+					Ok(ret) => {
+						println!("{} occurence found.", ret.len());
+						if !options.hide {
+							parser.print_orders(&ret, "");
+						}
+						if options.save {
+							let savepath = if options.savepath.is_empty() { "log_search.log" } else { options.savepath.as_str() };
+							parser.save_to_file(&ret, savepath);
+						}
+					},
+					Err(e) => println!("{}", e),
+				}
 			}
 
 			// 若沒有搜尋條件但指定 --pki 時，輸出所有記錄的 PKI 格式到檔案
 		if options.pki_output && options.field.is_empty() {
-			let pki_output = parser.get_pki_output();
-			if !pki_output.is_empty() {
-				let now = chrono::Local::now();
-				let filename = format!("PKILog-{}.log", now.format("%Y%m%d"));
-				// 清空前次執行的內容
-				let _ = fs::write(&filename, "");
-				if let Ok(mut file) = OpenOptions::new()
-					.create(true)
-					.append(true)
-					.open(&filename) {
-					let _ = file.write_all(pki_output.as_bytes());
-					println!("PKI output saved to: {}", filename);
-				}
+			let lines = parser.get_pki_records();
+			let rejects = parser.take_pki_rejects(&filepath);
+			if save_pki_output(&pki::default_output_file(), &filepath, lines, rejects, &layout, options.pki_envelope) > 0 {
+				std::process::exit(PKI_REJECT_EXIT_CODE);
 			}
 			return Ok(());
 		}
@@ -299,6 +626,8 @@ fn main() -> Result<()> {
 				}
 			}
 
+			print_reports(&parser, &options);
+
 			// 顯示每秒流量
 			if options.show_flow {
 				println!("{}", parser.req_flow_statistic());
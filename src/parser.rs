@@ -1,20 +1,37 @@
+use std::collections::BTreeMap;
 use std::collections::HashMap;
 use std::collections::HashSet;
 use std::collections::LinkedList;
 use std::fmt;
-use std::rc::Rc;
+use std::sync::{Arc, OnceLock};
 use chrono::prelude::*;
-use std::fs::{File, OpenOptions};
+use std::fs::File;
 use std::io::prelude::*;
+use std::io::IsTerminal;
 use chrono::LocalResult::Single;
+use serde::{Deserialize, Serialize};
+use crate::codes;
+use crate::logindex::LogIndex;
+use crate::pki::{self, PkiLayout, PkiLine, PkiReject, PkiSeverity};
+
+/// 搜尋條件中代表 log 欄位的表名，例如 log:Tag1:A
+pub const LOG_TABLE: &str = "log";
+
+/// pretty_rec 顯示 digsgn 開頭的字數
+const DIGSGN_HEAD_CHARS: usize = 32;
+
+/// 委託失敗的 OrderSt / ReqStep
+const ST_FAILED: &str = "99";
 
 // 每一筆資料由 string array組成每一個欄位，原資料ReqOrd, 以及相關的log
+#[derive(Serialize, Deserialize)]
 pub struct Rec {
 	reqs_vec: Vec<String>,
 	line    : String,
 	log     : String,
 	linked  : bool,
 	digsgn  : String,  // 簽章資訊，從 ':' 開頭行的最後欄位提取
+	line_no : usize,   // 在 log 檔中的行號 (從 1 開始)
 }
 
 impl Rec {
@@ -24,9 +41,37 @@ impl Rec {
 	pub fn is_req(&self) -> bool {
 		self.get_field(0) == "Req"
 	}
+	/// Req 或 Ord 的 key
+	pub fn get_key(&self) -> &str {
+		self.get_field(1)
+	}
+	/// 記錄所屬的表名
+	pub fn get_table(&self) -> &str {
+		self.get_field(2)
+	}
+	/// Ord 對應的 Req key (field 4)
+	pub fn get_req_key(&self) -> &str {
+		self.get_field(4)
+	}
 	pub fn get_digsgn(&self) -> &str {
 		&self.digsgn
 	}
+	/// 在 log 檔中的行號
+	pub fn get_line_no(&self) -> usize {
+		self.line_no
+	}
+	/// 欄位數
+	pub fn field_count(&self) -> usize {
+		self.reqs_vec.len()
+	}
+	/// field 3 的原始內容
+	pub fn get_time_field(&self) -> &str {
+		self.get_field(3)
+	}
+	/// field 3 的時間 (秒, 含小數)
+	pub fn get_secs(&self) -> Option<f64> {
+		self.reqs_vec.get(3).and_then(|t| t.parse::<f64>().ok())
+	}
 	pub fn get_timestamp(&self) -> String {
 		let mut dt = String::new();
 		if self.reqs_vec.len() > 3 {
@@ -69,6 +114,52 @@ impl Rec {
 		}
 		dt
 	}
+	/// 附加在此記錄之後的 log, 每行以 '\n' 分隔
+	pub fn get_log(&self) -> &str {
+		&self.log
+	}
+	/// ':' 開頭的 log 行中 Tag=Value 形式的欄位 (依出現順序)
+	/// 欄位以 \x01 分隔，沒有 '=' 的欄位 (例如最後的簽章) 不列入
+	pub fn get_log_fields(&self) -> Vec<(String, String)> {
+		self.log.lines()
+			.filter_map(|line| line.strip_prefix(':'))
+			.flat_map(|line| line.split('\x01'))
+			.filter_map(|tok| tok.split_once('='))
+			.filter(|(tag, _)| !tag.trim().is_empty())
+			.map(|(tag, value)| (tag.trim().to_string(), value.to_string()))
+			.collect()
+	}
+	/// log 欄位的值，同一欄位出現多次時取第一個，沒有時為空字串
+	pub fn get_log_value(&self, tag: &str) -> String {
+		self.get_log_fields().into_iter().find(|(t, _)| t == tag).map(|(_, v)| v).unwrap_or_default()
+	}
+	/// 附加的 log 各行 (不含空行)，欄位分隔字元 \x01 以 | 表示
+	pub fn get_log_lines(&self) -> Vec<String> {
+		self.log.lines().filter(|l| !l.is_empty()).map(|l| l.replace('\x01', "|")).collect()
+	}
+	/// 原始記錄行，欄位分隔字元 \x01 以 | 表示
+	pub fn get_raw_line(&self) -> String {
+		self.line.replace('\x01', "|")
+	}
+	/// digsgn 的長度及開頭，過長的簽章只顯示前 DIGSGN_HEAD_CHARS 字
+	pub fn get_digsgn_head(&self) -> String {
+		let len = self.digsgn.chars().count();
+		if len > DIGSGN_HEAD_CHARS {
+			format!("({} chars) {}...", len, self.digsgn.chars().take(DIGSGN_HEAD_CHARS).collect::<String>())
+		} else {
+			format!("({} chars) {}", len, self.digsgn)
+		}
+	}
+	/// 記錄的說明: Req 為要求種類(field 4), Ord 為委託狀態(field 6)，見 codes
+	pub fn get_label(&self) -> String {
+		if self.is_req() {
+			codes::label(self.get_table(), "ReqKind", self.get_field(4))
+		} else if !self.get_field(6).is_empty() {
+			codes::label(self.get_table(), "OrderSt", self.get_field(6))
+		} else {
+			String::new()
+		}
+	}
 	pub fn print(&self) {
 		print!("{}", self);
 	}
@@ -79,13 +170,10 @@ impl fmt::Display for Rec {
 		let mut ret = String::new();
 		if self.reqs_vec.len() > 5 {
 			if self.is_req() {
-				let type_key = self.get_field(4);
-				let ord_type: &str = match type_key
-				{ "1" => "新單", "2" => "改量", "3" => "改價", "4" => "刪單", "10" =>"成交", _=> "" };
-				ret = format!("{} ({})\n", self.get_timestamp(), ord_type);
+				ret = format!("{} ({})\n", self.get_timestamp(), self.get_label());
 			} 
-			else if let Ok(st) = self.get_field(6).parse::<i32>() {
-					ret = format!("{} =>{}\n", self.get_timestamp(), get_ordst(st));
+			else if !self.get_label().is_empty() {
+					ret = format!("{} =>{}\n", self.get_timestamp(), self.get_label());
 				} else {
 					ret = format!("{}\n", self.get_timestamp());
 				}
@@ -95,20 +183,7 @@ impl fmt::Display for Rec {
 	}
 }
 
-fn get_ordst(st: i32) -> &'static str {
-	match st {
-		6 => "委託傳送中",
-		7 => "委託已傳送",
-		90=> "委託成功",
-		99 => "委託失敗",
-		101 => "交易所已接受",
-		110 => "部份成交",
-		111 => "全部成交",
-		120 => "交易所取消",
-		_ => "未知",
-	}
-}
-
+#[derive(Serialize, Deserialize)]
 pub struct TableRec {
 	pub index: HashMap<String, usize>,
 	pub recs : Vec<String>,
@@ -129,14 +204,20 @@ impl TableRec {
 	}
 }
 
-type ReqRecMap   = HashMap<String, Rc<Rec>>;            // ReqKey-Rc<Rec>
-type OrdRecMap   = HashMap<String, Vec<Rc<Rec>>>;       // OrdKey-Vec<Rc<Rec>>
+type ReqRecMap   = HashMap<String, Arc<Rec>>;            // ReqKey-Arc<Rec>
+type OrdRecMap   = HashMap<String, Vec<Arc<Rec>>>;       // OrdKey-Vec<Arc<Rec>>
 
+#[derive(Serialize, Deserialize)]
 pub struct OrderRec {
 	pub tables : HashMap<String, TableRec>, // table_name-table fields
 	pub reqs   : ReqRecMap,
 	pub ords   : OrdRecMap,
 	req2ord: HashMap<String, String>,   // req對應到的ord
+	pub overwritten: Vec<Arc<Rec>>,     // 被相同 key 的 Req 覆蓋掉的 Req
+	#[serde(skip)]
+	log_index: OnceLock<LogIndex>,      // 附加 log 的索引，第一次全文搜尋時建立
+	#[serde(skip)]
+	pub quiet: bool,                    // 不印出查詢時找不到的記錄 (終端機介面使用)
 }
 
 pub struct OrdInfo {
@@ -153,6 +234,15 @@ impl OrdInfo {
 			status: String::new(),
 		}
 	}
+	pub fn rid(&self) -> &str {
+		&self.rid
+	}
+	pub fn ordno(&self) -> &str {
+		&self.ordno
+	}
+	pub fn status(&self) -> &str {
+		&self.status
+	}
 }
 
 impl fmt::Display for OrdInfo {
@@ -169,9 +259,14 @@ impl OrderRec {
 			reqs  : ReqRecMap::new(),                   // reqKey-一筆Req
 			ords  : OrdRecMap::new(),                   // ordKey-一筆Ord
 			req2ord: HashMap::<String, String>::new(),
+			overwritten: Vec::new(),
+			log_index: OnceLock::new(),
+			quiet: false,
 		}
 	}
-	pub fn insert_rec(&mut self, toks: Vec<String>, line: &str, log: &str, digsgn: &str) -> (&'static str, String) {
+	pub fn insert_rec(&mut self, toks: Vec<String>, line: &str, log: &str, digsgn: &str, line_no: usize) -> (&'static str, String) {
+		// 記錄有變動，已建立的 log 索引失效
+		self.log_index.take();
 		// 先提取所有需要的值，避免借用問題
 		let key_str = toks.get(1).cloned().unwrap_or_default();
 		let hdr = toks.first().cloned().unwrap_or_default();
@@ -186,13 +281,16 @@ impl OrderRec {
 			tabrec.recs = toks;
 		}
 		else if hdr == "Req" {  // 依key將記錄儲存到hashmap中
-			let rec = Rc::new(Rec{reqs_vec: toks, line: line.to_string(), log: log.to_string(), linked: false, digsgn: digsgn.to_string()});
-			self.reqs.insert(key_str.clone(), rec);
+			let rec = Arc::new(Rec{reqs_vec: toks, line: line.to_string(), log: log.to_string(), linked: false, digsgn: digsgn.to_string(), line_no});
+			// 相同 key 的 Req 會覆蓋前一筆，留下被覆蓋的記錄供檢查 (見 check)
+			if let Some(old) = self.reqs.insert(key_str.clone(), rec) {
+				self.overwritten.push(old);
+			}
 			return ("Req", key_str)
 		}
 		else if hdr == "Ord" {	
-			let rec = Rc::new(Rec{reqs_vec: toks, line: line.to_string(), log: log.to_string(), linked: false, digsgn: digsgn.to_string()});
-			self.ords.entry(key_str.clone()).or_default().push(Rc::clone(&rec));
+			let rec = Arc::new(Rec{reqs_vec: toks, line: line.to_string(), log: log.to_string(), linked: false, digsgn: digsgn.to_string(), line_no});
+			self.ords.entry(key_str.clone()).or_default().push(Arc::clone(&rec));
 			// 檢查Req-Ord對應是否有覆蓋的情況
 			if let Some(ordkey) = self.req2ord.get(&reqkey_str) {
 					if ordkey != &key_str {
@@ -200,7 +298,7 @@ impl OrderRec {
 					}
 				}
 			self.req2ord.insert(reqkey_str.clone(), key_str.clone());
-			// 注意: Rc 內部不可變，無法修改 linked
+			// 注意: Arc 內部不可變，無法修改 linked
 			return ("Ord", key_str)
 		}
 		else {
@@ -209,24 +307,42 @@ impl OrderRec {
 		("", "".to_string())
 	}
 	/// 取得 指定Ord key 的 ReqOrd 的 LinkedList
-	pub fn get_target_ordlist(&self, key: &str) -> LinkedList<Rc<Rec>> {
-		let mut reqord_list = LinkedList::<Rc<Rec>>::new();
+	pub fn get_target_ordlist(&self, key: &str) -> LinkedList<Arc<Rec>> {
+		let mut reqord_list = LinkedList::<Arc<Rec>>::new();
 		if let Some(list) = self.ords.get(key) {
 				let mut reqkey: &str = "";
 				for ord in list {
 					let ord_reqkey = ord.get_field(4);
 					if reqkey != ord_reqkey {
 						match self.reqs.get(ord_reqkey) {
-							Some(rec) => reqord_list.push_back(Rc::clone(rec)),
-							_=> println!("req {} not found", ord_reqkey),
+							Some(rec) => reqord_list.push_back(Arc::clone(rec)),
+							_=> if !self.quiet {
+								println!("req {} not found", ord_reqkey);
+							},
 						}
 						reqkey = ord_reqkey;
 					};
-					reqord_list.push_back(Rc::clone(ord));
+					reqord_list.push_back(Arc::clone(ord));
 				}
 			};
 		reqord_list
 	}
+	/// 取得 Req 對應的 Ord key
+	pub fn get_ord_key(&self, req_key: &str) -> Option<&str> {
+		self.req2ord.get(req_key).map(|s| s.as_str())
+	}
+	/// 依記錄所屬的 TableRec 取得所有 欄位名-值，沒有對應欄位名的以 #index 表示
+	pub fn get_fields(&self, rec: &Rec) -> Vec<(String, String)> {
+		let names = self.tables.get(rec.get_field(2)).map(|tab| &tab.recs);
+		rec.reqs_vec.iter().enumerate().skip(3).map(|(idx, val)| {
+			let name = names.and_then(|n| n.get(idx)).cloned().unwrap_or_else(|| format!("#{}", idx));
+			(name, val.clone())
+		}).collect()
+	}
+	/// Ord 是否為委託失敗: OrderSt 或 ReqStep 為 99
+	pub fn is_failed(&self, ord: &Rec) -> bool {
+		self.get_value(ord, "OrderSt") == ST_FAILED || self.get_value(ord, "ReqStep") == ST_FAILED
+	}
 	/// 取得該記錄中，指定欄位的值
 	pub fn get_value(&self, rec: &Rec, field_name: &str) -> String {
 		if rec.reqs_vec.len() > 2 {
@@ -248,6 +364,21 @@ impl OrderRec {
 	}
 	/// 統計某一欄位的數量: 例如TwfNew總共有多少個user
 	pub fn statistic_field(&self, table_name: &str, field_name: &str) -> String {
+		match self.field_values(table_name, field_name) {
+			Ok(field_set) => {
+				let mut ret= format!("there are totally {} {} of {}:\n", field_set.len(), field_name, table_name);
+				for user in field_set {							
+					ret.push_str(&user);
+					ret.push('\n');
+				}
+				ret
+			},
+			Err(e) => e,
+		}
+	}
+	/// 某一表格的某一欄位, 在所有 Req 中出現過的值 (不含空值)
+	/// 表格或欄位不存在時回傳說明
+	pub fn field_values(&self, table_name: &str, field_name: &str) -> Result<HashSet<String>, String> {
 		let mut field_set = HashSet::<String>::new();
 		match self.tables.get(table_name) { // 先從tables中, 找到要的table(例如TwfNew)
 			Some(tabrec) => {
@@ -263,24 +394,20 @@ impl OrderRec {
 									}
 								}
 						}
-						let mut ret= format!("there are totally {} {} of {}:\n", field_set.len(), field_name, table_name);
-						for user in field_set {							
-							ret.push_str(&user);
-							ret.push('\n');
-						}
-						ret
+						Ok(field_set)
 					},
-					_=> format!("there is no {} field", field_name),
+					_=> Err(format!("there is no {} field", field_name)),
 				}
 			},
-			_=> format!("there is no {} table", table_name),
+			_=> Err(format!("there is no {} table", table_name)),
 		}
 	}
 	/// 取得該筆LinkedList的彙總說明
-	fn get_ord_summary(&self, list: &LinkedList<Rc<Rec>>) -> OrdInfo {
+	pub fn get_ord_summary(&self, list: &LinkedList<Arc<Rec>>) -> OrdInfo {
 		let mut info = OrdInfo::new();
 		let mut ordst :i32 = 0;
 		let mut reqst :i32 = 0;
+		let mut table = "";
 		for rec in list {
 			if rec.get_field(0) == "Req" && rec.get_field(4) == "1" { // 若是新單要求，則取流水號
 				info.rid = self.get_value(rec, "SorRID");
@@ -290,6 +417,7 @@ impl OrderRec {
 				if let Ok(st) = self.get_value(rec, "OrderSt").parse::<i32>() {
 					if st > ordst {
 						ordst = st;
+						table = rec.get_table();
 						if let Ok(rst) = self.get_value(rec, "ReqStep").parse::<i32>() {
 							reqst = rst;
 						}
@@ -297,26 +425,30 @@ impl OrderRec {
 				}
 			}
 		}
-		info.status = get_ordst(reqst).to_string();
+		let code = |st: i32| if st == 0 { String::new() } else { st.to_string() };
+		info.status = codes::label(table, "ReqStep", &code(reqst));
 		info.status.push('/');
-		info.status.push_str(get_ordst(ordst));
+		info.status.push_str(&codes::label(table, "OrderSt", &code(ordst)));
 		info
 	}
 	
 
 	
-	/// 檢查rec是否符合條件
-	pub fn check_rec(&self, rec: &Rec, table_name: &str, key_index: usize, target: &str) -> Option<String> {
+	/// 檢查rec是否符合條件，符合時傳回所屬委託的 ReqOrd list；Req 尚無對應的 Ord 時只有該 Req
+	pub fn check_rec(&self, rec: &Arc<Rec>, table_name: &str, key_index: usize, target: &str) -> Option<LinkedList<Arc<Rec>>> {
 		if  rec.reqs_vec.len() < 3 || rec.get_field(2) != table_name {
 			return None;
 		}
 		if rec.reqs_vec.len() > key_index
 			&& rec.get_field(key_index) == target {
-				let key_str = rec.get_field(1).to_string();
+				let key_str = rec.get_field(1);
 				if rec.get_field(0) == "Ord" {
-					return Some(key_str);
+					return Some(self.get_target_ordlist(key_str));
 				} else if rec.get_field(0) == "Req" {
-					return Some(self.req2ord[&key_str].to_string());
+					return match self.req2ord.get(key_str) {
+						Some(ord_key) => Some(self.get_target_ordlist(ord_key)),
+						None => Some(LinkedList::from([Arc::clone(rec)])),
+					};
 				} else {
 					return None;
 				}
@@ -332,7 +464,7 @@ impl OrderRec {
 		}
 	}*/
 	/// 將ord list轉為字串
-	pub fn ord_list_to_string(&self, list: &LinkedList<Rc<Rec>>) -> String {
+	pub fn ord_list_to_string(&self, list: &LinkedList<Arc<Rec>>) -> String {
 		let mut list_str = String::new();
 		list_str.push_str(&format!("{}", self.get_ord_summary(list)));
 		list_str.push('\n');
@@ -341,8 +473,126 @@ impl OrderRec {
 		}
 		list_str
 	}
+	/// 將ord list轉為 JSON: 彙總及每筆 Req/Ord 的欄位、log
+	pub fn ord_list_to_json(&self, list: &LinkedList<Arc<Rec>>) -> serde_json::Value {
+		let info = self.get_ord_summary(list);
+		let records: Vec<serde_json::Value> = list.iter().map(|rec| {
+			let fields: serde_json::Map<String, serde_json::Value> = self.get_fields(rec).into_iter()
+				.map(|(name, value)| (name, serde_json::Value::String(value)))
+				.collect();
+			let log_fields: serde_json::Map<String, serde_json::Value> = rec.get_log_fields().into_iter()
+				.map(|(tag, value)| (tag, serde_json::Value::String(value)))
+				.collect();
+			serde_json::json!({
+				"type"     : rec.get_field(0),
+				"key"      : rec.get_key(),
+				"table"    : rec.get_table(),
+				"timestamp": rec.get_timestamp(),
+				"label"    : rec.get_label(),
+				"fields"   : fields,
+				"log_fields": log_fields,
+				"log"      : rec.get_log(),
+			})
+		}).collect();
+		serde_json::json!({
+			"ord_key": list.iter().find(|rec| !rec.is_req()).map(|rec| rec.get_key()),
+			"rid"    : info.rid,
+			"ordno"  : info.ordno,
+			"status" : info.status,
+			"records": records,
+		})
+	}
+	/// 附加 log 的索引，尚未建立時先建立
+	pub fn log_index(&self) -> &LogIndex {
+		self.log_index.get_or_init(|| LogIndex::build(self.reqs.values().chain(self.ords.values().flatten())))
+	}
+	/// 記錄所屬的委託，依記錄的時間排序，同一委託只列一次；Req 尚無對應的 Ord 時只列該 Req
+	pub fn owning_orders(&self, mut recs: Vec<&Arc<Rec>>) -> LinkedList<LinkedList<Arc<Rec>>> {
+		recs.sort_by(|a, b| a.get_secs().unwrap_or(0.0).total_cmp(&b.get_secs().unwrap_or(0.0)));
+		let mut seen = HashSet::<(bool, &str)>::new();
+		let mut ret = LinkedList::<LinkedList<Arc<Rec>>>::new();
+		for rec in recs {
+			let ord_key = if rec.is_req() { self.get_ord_key(rec.get_key()) } else { Some(rec.get_key()) };
+			match ord_key {
+				Some(key) => if seen.insert((false, key)) {
+					ret.push_back(self.get_target_ordlist(key));
+				},
+				None => if seen.insert((true, rec.get_key())) {
+					ret.push_back(LinkedList::from([Arc::clone(rec)]));
+				},
+			}
+		}
+		ret
+	}
+	/// log 欄位 tag 的值為 value 的記錄所屬的委託 (條件 log:Tag:Value)
+	pub fn find_log_field(&self, tag: &str, value: &str) -> LinkedList<LinkedList<Arc<Rec>>> {
+		let recs: Vec<&Arc<Rec>> = self.reqs.values().chain(self.ords.values().flatten())
+			.filter(|rec| rec.get_log_fields().iter().any(|(t, v)| t == tag && v == value))
+			.collect();
+		self.owning_orders(recs)
+	}
+	/// 所有記錄的 log 欄位名，及含有該欄位的記錄數
+	pub fn log_tags(&self) -> BTreeMap<String, usize> {
+		let mut tags = BTreeMap::<String, usize>::new();
+		for rec in self.reqs.values().chain(self.ords.values().flatten()) {
+			let rec_tags: HashSet<String> = rec.get_log_fields().into_iter().map(|(tag, _)| tag).collect();
+			for tag in rec_tags {
+				*tags.entry(tag).or_default() += 1;
+			}
+		}
+		tags
+	}
+	/// 記錄中符合搜尋條件 (語法同 find_by_conditions) 的欄位名
+	pub fn matched_fields(&self, rec: &Rec, condstr: &str) -> HashSet<String> {
+		condstr.split([',', '|'])
+			.filter_map(|cond| {
+				let toks: Vec<&str> = cond.trim().split(':').collect();
+				if toks.len() > 2 { Some((toks[0], toks[1], toks[2])) } else { None }
+			})
+			.filter(|(table, field, value)| *table == rec.get_table() && self.get_value(rec, field) == *value)
+			.map(|(_, field, _)| field.to_string())
+			.collect()
+	}
+	/// 以表格的欄位名對齊列出記錄: 每行一個 欄位名 = 值，符合條件的欄位以 * 標示 (color 時另加顏色)
+	/// 附加的 log 及 digsgn 摺疊在下方，digsgn 只顯示開頭及長度
+	pub fn pretty_rec(&self, rec: &Rec, condstr: &str, color: bool) -> String {
+		let kind = if rec.is_req() { "Req" } else { "Ord" };
+		let mut ret = format!("{} {} {} {} ({})\n", rec.get_timestamp(), kind, rec.get_key(), rec.get_table(), rec.get_label());
+		let fields = self.get_fields(rec);
+		let matched = self.matched_fields(rec, condstr);
+		let width = fields.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+		for (name, value) in &fields {
+			let line = format!("{:<width$} = {}", name, value, width = width);
+			if !matched.contains(name) {
+				ret.push_str(&format!("      {}\n", line));
+			} else if color {
+				ret.push_str(&format!("    * \x1b[1;33m{}\x1b[0m\n", line));
+			} else {
+				ret.push_str(&format!("    * {}\n", line));
+			}
+		}
+		let log_lines = rec.get_log_lines();
+		if !log_lines.is_empty() {
+			ret.push_str(&format!("  + log ({} lines)\n", log_lines.len()));
+			for line in log_lines {
+				ret.push_str(&format!("      | {}\n", line));
+			}
+		}
+		if !rec.get_digsgn().is_empty() {
+			ret.push_str(&format!("  + digsgn {}\n", rec.get_digsgn_head()));
+		}
+		ret
+	}
+	/// 將ord list以 pretty_rec 的格式轉為字串
+	pub fn pretty_ord_list(&self, list: &LinkedList<Arc<Rec>>, condstr: &str, color: bool) -> String {
+		let mut list_str = format!("{}\n", self.get_ord_summary(list));
+		for rec in list {
+			list_str.push_str(&self.pretty_rec(rec, condstr, color));
+		}
+		list_str
+	}
 	/// 印出 Ord list 的 彙總以及 所有Log; 每筆Log會有timestamp
-	pub fn print_ord_list(&self, list: &LinkedList<Rc<Rec>>) {
+	pub fn print_ord_list(&self, list: &LinkedList<Arc<Rec>>) {
 		println!("{}", self.get_ord_summary(list));
 		for rec in list {
 			rec.print();
@@ -350,17 +600,17 @@ impl OrderRec {
 	}
 	/// 從前一次的搜尋結果中, 以給定的條件再次搜尋
 	#[allow(dead_code)]
-	pub fn find_list(&self, list_of_list: LinkedList<LinkedList<Rc<Rec>>>, table_name: &str, field_name: &str, search_target: &str) -> Option<LinkedList<LinkedList<Rc<Rec>>>> {
+	pub fn find_list(&self, list_of_list: LinkedList<LinkedList<Arc<Rec>>>, table_name: &str, field_name: &str, search_target: &str) -> Option<LinkedList<LinkedList<Arc<Rec>>>> {
 		println!("checking {}, {}", field_name, search_target);
-		let mut result_list = LinkedList::<LinkedList<Rc<Rec>>>::new();
+		let mut result_list = LinkedList::<LinkedList<Arc<Rec>>>::new();
 		match self.tables.get(table_name) {
 			Some(tabrec) => { // 有對應到指定的table
 				match tabrec.index.get(field_name) {
 					Some(idx) => {  // 有對應到指定的filed
 						for list in list_of_list { // 從給定的list of list裡搜尋每一筆list
 							for rec in list {       // 比對list裡的每一筆 rec
-								if let Some(key) = self.check_rec(&rec, table_name, *idx, search_target) {
-									result_list.push_back(self.get_target_ordlist(&key)); // 有找到的話存進結果裡
+								if let Some(ord_list) = self.check_rec(&rec, table_name, *idx, search_target) {
+									result_list.push_back(ord_list); // 有找到的話存進結果裡
 									break;
 								}
 							}
@@ -374,52 +624,54 @@ impl OrderRec {
 		Some(result_list)
 	}
 	/// 以index, 找出ords中相等於target的rec
-	pub fn find_req(&self, table_name: &str, key_index: usize, target: &str) -> LinkedList<LinkedList<Rc<Rec>>> {
+	pub fn find_req(&self, table_name: &str, key_index: usize, target: &str, quiet: bool) -> LinkedList<LinkedList<Arc<Rec>>> {
 		let mut found = false;
-		let mut list_of_list = LinkedList::<LinkedList<Rc<Rec>>>::new();
+		let mut list_of_list = LinkedList::<LinkedList<Arc<Rec>>>::new();
 		for rec in self.reqs.values()  {
 			match self.check_rec(rec, table_name, key_index, target)
 			{
-				Some(key) => { 
+				Some(ord_list) => { 
 					//self.print_ord(&key);
-					list_of_list.push_back(self.get_target_ordlist(&key));
+					list_of_list.push_back(ord_list);
 					found = true; 
 				},
 				None      => continue,
 			}
 		};
-		if !found {
+		if !found && !quiet {
 			println!("{} not found", target);
 		}
 		list_of_list
 	}
 	/// 以index, 找出reqs中相等於target的rec
-	pub fn find_ord(&self, table_name: &str, key_index: usize, target: &str) -> LinkedList<LinkedList<Rc<Rec>>> {
+	pub fn find_ord(&self, table_name: &str, key_index: usize, target: &str, quiet: bool) -> LinkedList<LinkedList<Arc<Rec>>> {
 		let mut found = false;
-		let mut list_of_list = LinkedList::<LinkedList<Rc<Rec>>>::new();
+		let mut list_of_list = LinkedList::<LinkedList<Arc<Rec>>>::new();
 		for (key, list) in &self.ords {
 			match list.last() {
 				Some(rec) => {
 					match self.check_rec(rec, table_name, key_index, target)
 					{
-						Some(key) => { 
+						Some(ord_list) => { 
 							//self.print_ord(&key);
-							list_of_list.push_back(self.get_target_ordlist(&key));
+							list_of_list.push_back(ord_list);
 							found = true;
 						},
 						None      => continue,
 					}
 				},
-				_=>println!("{} is empty", key),
+				_=> if !quiet {
+					println!("{} is empty", key);
+				},
 			}
 		}
-		if !found {
+		if !found && !quiet {
 			println!("{} not found", target);
 		}
 		list_of_list
 	}
 
-	pub fn check_req_data(&self, table_name: &str, field_name: &str, search_target: &str, hide: &bool, quiet: bool) -> Option<LinkedList<LinkedList<Rc<Rec>>>> {
+	pub fn check_req_data(&self, table_name: &str, field_name: &str, search_target: &str, hide: &bool, quiet: bool) -> Option<LinkedList<LinkedList<Arc<Rec>>>> {
 		if !quiet {
 			println!("checking {}, {}", field_name, search_target);
 		}
@@ -433,10 +685,10 @@ impl OrderRec {
 				match tabrec.index.get(field_name) {
 					Some(idx) => {
 						if tabrec.recs[0] == "Req" {
-							return Some(self.find_req(table_name, *idx, search_target));
+							return Some(self.find_req(table_name, *idx, search_target, quiet));
 						}
 						else if tabrec.recs[0] == "Ord" {
-							return Some(self.find_ord(table_name, *idx, search_target));
+							return Some(self.find_ord(table_name, *idx, search_target, quiet));
 						}
 						else if !quiet {
 								println!("cannot find {}, {}", field_name, search_target);
@@ -459,11 +711,24 @@ impl OrderRec {
 	}
 }
 
+/// 記錄的數量統計, 見 get_info
+pub struct LogCounts {
+	pub tables : usize,
+	pub reqs   : usize,
+	pub ords   : usize,
+	pub deals  : usize,
+	pub invalid: usize,
+}
+
 // 4. 解析管理
 pub struct Parser {
 	pub ord_rec : OrderRec,
 	info    : String,
-	prevkey : (&'static str, String)
+	prevkey : (&'static str, String),
+	pki_layout: PkiLayout,
+	pki_rejects: Vec<PkiReject>,
+	pki_lines : Vec<PkiLine>,
+	pretty  : bool,
 }
 /*
 pub struct Conditions {
@@ -479,31 +744,56 @@ impl Parser {
 			ord_rec: OrderRec::new(),
 			info   : String::new(),
 			prevkey: ("", "".to_string()),
+			pki_layout: PkiLayout::default(),
+			pki_rejects: Vec::new(),
+			pki_lines : Vec::new(),
+			pretty  : false,
 		}
 	}
 
-	///取得統計資訊
-	pub fn get_info(&mut self) -> &str {
-		if self.info.is_empty() {
-			let mut deals = 0;
-			let mut fails = 0;
-			// 掃描req列表，統計
-			for req in self.ord_rec.reqs.values() {
-				if req.get_field(4) == "10" || req.get_field(4) == "11" {
-					deals += 1;
-				}
+	/// 設定PKI輸出的版面
+	pub fn set_pki_layout(&mut self, layout: PkiLayout) {
+		self.pki_layout = layout;
+	}
+	/// 搜尋結果以 欄位名 = 值 對齊列出 (見 OrderRec::pretty_rec)
+	pub fn set_pretty(&mut self, pretty: bool) {
+		self.pretty = pretty;
+	}
+
+	///取得統計數量
+	pub fn get_counts(&self) -> LogCounts {
+		let mut deals = 0;
+		let mut fails = 0;
+		// 掃描req列表，統計
+		let deal_kinds = &codes::book().fills.deal_kinds;
+		for req in self.ord_rec.reqs.values() {
+			if deal_kinds.iter().any(|kind| kind == req.get_field(4)) {
+				deals += 1;
 			}
-			// 掃描req列表，統計
-			for ord in self.ord_rec.ords.values() {
-				if let Some(rec) = ord.last() {
-					if rec.reqs_vec.len() > 7 && rec.get_field(7) == "99" {
-						fails += 1;
-					}
+		}
+		// 掃描req列表，統計
+		for ord in self.ord_rec.ords.values() {
+			if let Some(rec) = ord.last() {
+				if self.ord_rec.is_failed(rec) {
+					fails += 1;
 				}
 			}
+		}
+		LogCounts {
+			tables : self.ord_rec.tables.len(),
+			reqs   : self.ord_rec.reqs.len(),
+			ords   : self.ord_rec.ords.len(),
+			deals,
+			invalid: fails,
+		}
+	}
+
+	///取得統計資訊
+	pub fn get_info(&mut self) -> &str {
+		if self.info.is_empty() {
+			let counts = self.get_counts();
 			self.info = format!("tables:\t{}\nreqs:\t{}\nords:\t{}\ndeals:\t{}\ninvalid:\t{}\n", 
-				self.ord_rec.tables.len(), self.ord_rec.reqs.len(), self.ord_rec.ords.len(),
-				deals, fails);
+				counts.tables, counts.reqs, counts.ords, counts.deals, counts.invalid);
 			
 			&self.info
 		}
@@ -514,7 +804,7 @@ impl Parser {
 
 	/// 回傳未連結的Req的統計資料
 	pub fn list_unlink_req(&mut self) -> String {
-		let unlinked_req: Vec<(&String, &Rc<Rec>)> = self.ord_rec.reqs.iter().filter(|v| !v.1.linked ).collect();
+		let unlinked_req: Vec<(&String, &Arc<Rec>)> = self.ord_rec.reqs.iter().filter(|v| !v.1.linked ).collect();
 		let mut ret = String::new();
 		if !unlinked_req.is_empty() {
 			let cnt_str = format!("count:{}\n", unlinked_req.len());
@@ -536,6 +826,18 @@ impl Parser {
 
 	pub fn req_flow_statistic(&self) -> String {
 		let mut ret = String::new();
+		// 印出結果
+		for (t, cnt) in self.req_flow() {
+			if let Single(datetime) = Local.timestamp_opt(t, 0) {
+				let tmstr = format!("{}, {},{}\n", t, datetime.format("%Y%m%d%H%M%S"), cnt);
+				ret.push_str(&tmstr);
+			}
+		}
+		ret
+	}
+
+	/// 每秒的req數量, 依時間排序 (秒, 數量)
+	pub fn req_flow(&self) -> Vec<(i64, i32)> {
 		// 建一個統計流量的hasp map
 		let mut flow_map = HashMap::<i64, i32>::new();
 		// 取全部的req, 取出其中的timestamp, 拆出整數部份(秒), 填入haspmap中統計次數
@@ -555,88 +857,184 @@ impl Parser {
 		// 將hashmap轉為Vec
 		let mut sort_map = flow_map.into_iter().collect::<Vec<_>>();
 		// 將Vec排序
-		sort_map.sort_by(|a, b| a.0.cmp(&b.0));
-		// 印出結果
-		for (t, cnt) in sort_map {
-			if let Single(datetime) = Local.timestamp_opt(t, 0) {
-				let tmstr = format!("{}, {},{}\n", t, datetime.format("%Y%m%d%H%M%S"), cnt);
-				ret.push_str(&tmstr);
+		sort_map.sort_by_key(|a| a.0);
+		sort_map
+	}
+
+	/// log 欄位名及含有該欄位的記錄數
+	pub fn log_tags_statistic(&self) -> String {
+		let tags = self.ord_rec.log_tags();
+		let mut ret = format!("there are totally {} log tags:\n", tags.len());
+		for (tag, count) in tags {
+			ret.push_str(&format!("{}\t{}\n", tag, count));
+		}
+		ret
+	}
+
+	/// 依 Req 的某一欄位 (例如 User, SesName) 統計筆數, 由多到少排序
+	pub fn count_reqs_by(&self, field_name: &str) -> Vec<(String, usize)> {
+		let mut counts = HashMap::<String, usize>::new();
+		for req in self.ord_rec.reqs.values() {
+			let val = self.ord_rec.get_value(req, field_name);
+			if !val.is_empty() {
+				*counts.entry(val).or_default() += 1;
+			}
+		}
+		let mut ret: Vec<(String, usize)> = counts.into_iter().collect();
+		ret.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+		ret
+	}
+
+	/// 最後狀態為委託失敗 (見 OrderRec::is_failed) 的委託數, 依表名統計
+	pub fn count_failed_by_table(&self) -> Vec<(String, usize)> {
+		let mut counts = HashMap::<String, usize>::new();
+		for ord in self.ord_rec.ords.values() {
+			if let Some(rec) = ord.last() {
+				if self.ord_rec.is_failed(rec) {
+					*counts.entry(rec.get_table().to_string()).or_default() += 1;
+				}
 			}
 		}
+		let mut ret: Vec<(String, usize)> = counts.into_iter().collect();
+		ret.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
 		ret
 	}
 
+	/// 每筆 Req 到其第一筆 Ord 的時間差 (毫秒)
+	pub fn req_latencies(&self) -> Vec<f64> {
+		let mut first_ord = HashMap::<&str, f64>::new();
+		for ord in self.ord_rec.ords.values().flatten() {
+			if let Some(secs) = ord.get_secs() {
+				let ts = first_ord.entry(ord.get_field(4)).or_insert(secs);
+				if secs < *ts {
+					*ts = secs;
+				}
+			}
+		}
+		first_ord.iter()
+			.filter_map(|(req_key, ord_secs)| {
+				let req_secs = self.ord_rec.reqs.get(*req_key)?.get_secs()?;
+				Some((ord_secs - req_secs) * 1000.0)
+			})
+			.collect()
+	}
+
 	/// 統計某一欄位的數量: 例如TwfNew總共有多少個user
 	pub fn statistic_field(&self, table_name: &str, field_name: &str) -> String {
 		self.ord_rec.statistic_field(table_name, field_name)
 	}
 
-	/// 生成PKI格式輸出
-	/// 格式: |YYYYMMDD|BrkNo|Ivac(補0到7碼)|字元|FromUID(右補到15碼)|HHMMSS|digsgn
-	/// 從單一 Req 記錄生成 PKI 格式輸出
+	/// 依 pki_layout 的版面，從單一 Req 記錄生成 PKI 格式輸出
+	/// 預設格式: |YYYYMMDD|BrkNo|Ivac(補0到7碼)|字元|FromUID(右靠15碼)|HHMMSS|digsgn
 	/// first_req_key: 當欄位為空時，從這個 Req 取備用值
-	fn generate_pki_line(&self, req: &Rc<Rec>, first_req_key: Option<&Rc<Rec>>) -> Option<String> {
+	/// 未通過檢查的記錄不輸出，並回傳剔除原因；用了備用值的記錄會被標記
+	fn generate_pki_line(&self, req: &Arc<Rec>, first_req_key: Option<&Arc<Rec>>) -> (Option<PkiLine>, Option<PkiReject>) {
 		if !req.is_req() {
-			return None;
+			return (None, None);
 		}
 		
-		if self.ord_rec.get_value(req, "SesName") != "SorAPI" {
-			return None; // 只處理 SesName 為 SorAPI 的記錄
+		let layout = &self.pki_layout;
+		if !layout.filter_field.is_empty() && self.ord_rec.get_value(req, &layout.filter_field) != layout.filter_value {
+			return (None, None); // 只處理符合過濾條件(預設 SesName 為 SorAPI)的記錄
 		}
 		
 		let req_kind = self.ord_rec.get_value(req, "ReqKind");
-		
+		if layout.is_ignored_kind(&req_kind) {
+			return (None, None);
+		}
+		let mut reasons = Vec::<String>::new();
+		let mut flags = Vec::<String>::new();
 		// 根據 ReqKind 決定字元，只輸出已知的 ReqKind
-		let kind_char = match req_kind.as_str() {
-			"1" => "O",  // 新單
-			"4" => "C",  // 刪單
-			"2" | "3" => "M",  // 改量或改價
-			_ => return None  // 未知 ReqKind，跳過該記錄
+		let kind_char = match layout.kind_char(req.get_table(), &req_kind) {
+			Some(c) => c,
+			None => {
+				reasons.push(format!("unknown ReqKind {}", req_kind));
+				""
+			}
 		};
 		
-		let date = req.get_date();
-		let time = req.get_time();
-		let fromuid = self.ord_rec.get_value(req, "FromUID");
-		let mut brk_no = self.ord_rec.get_value(req, "BrkNo");
-		let mut ivac = self.ord_rec.get_value(req, "IvacNo");
-		let digsgn = req.get_digsgn();
-		
-		// 如果 BrkNo 或 IvacNo 為空，從 first_req_key 取值
-		if let Some(first_req) = first_req_key {
-			if brk_no.is_empty() {
-				brk_no = self.ord_rec.get_value(first_req, "BrkNo");
-			}
-			if ivac.is_empty() {
-				ivac = self.ord_rec.get_value(first_req, "IvacNo");
+		let mut values = Vec::<String>::with_capacity(layout.fields.len());
+		for field in &layout.fields {
+			let value = match field.source.as_str() {
+				pki::SRC_DATE   => req.get_date(),
+				pki::SRC_TIME   => req.get_time(),
+				pki::SRC_KIND   => kind_char.to_string(),
+				pki::SRC_DIGSGN => req.get_digsgn().to_string(),
+				name if name.starts_with(pki::SRC_LOG_PREFIX) => req.get_log_value(&name[pki::SRC_LOG_PREFIX.len()..]),
+				name => {
+					let mut val = self.ord_rec.get_value(req, name);
+					// 如果欄位為空，從 first_req_key 取值
+					if val.is_empty() && field.fallback {
+						if let Some(first_req) = first_req_key {
+							val = self.ord_rec.get_value(first_req, name);
+							if !val.is_empty() {
+								flags.push(format!("{} taken from req {}", field.name, first_req.get_field(1)));
+							}
+						}
+					}
+					val
+				}
+			};
+			if field.source != pki::SRC_KIND || !kind_char.is_empty() {
+				if let Some(reason) = field.validate(&value, &layout.separator) {
+					reasons.push(reason);
+				}
 			}
+			values.push(field.pad_value(&value));
 		}
 		
-		let ivac_padded = format!("{:0>7}", ivac);
-		let fromuid_padded = format!("{:>15}", fromuid);
-		let digsgn_padded = format!("{:<4096}", digsgn); // 補足至4096字元
-		Some(format!("|{}|{}|{}|{}|{}|{}|{}\n", date, brk_no, ivac_padded, kind_char, fromuid_padded, time, digsgn_padded))
+		let pki_line = PkiLine {
+			req_key: req.get_field(1).to_string(),
+			line   : layout.format_line(&values),
+		};
+		let severity = if !reasons.is_empty() {
+			PkiSeverity::Excluded
+		} else if !flags.is_empty() {
+			PkiSeverity::Flagged
+		} else {
+			return (Some(pki_line), None);
+		};
+		reasons.extend(flags);
+		let reject = PkiReject {
+			severity,
+			source   : String::new(),
+			req_key  : req.get_field(1).to_string(),
+			table    : req.get_field(2).to_string(),
+			timestamp: req.get_timestamp(),
+			reasons,
+		};
+		if severity == PkiSeverity::Excluded {
+			(None, Some(reject))
+		} else {
+			(Some(pki_line), Some(reject))
+		}
 	}
 
 	/// 從 Req 記錄列表生成 PKI 格式輸出
 	/// reqs: 要轉換的 Req 記錄列表
 	/// first_req_key: 當欄位為空時的備用值來源
-	fn generate_pki_from_reqs<'a, I>(&self, reqs: I, first_req_key: Option<&Rc<Rec>>) -> String 
+	/// rejects: 收集未通過檢查的記錄
+	fn generate_pki_from_reqs<'a, I>(&self, reqs: I, first_req_key: Option<&Arc<Rec>>, rejects: &mut Vec<PkiReject>) -> Vec<PkiLine> 
 	where
-		I: Iterator<Item = &'a Rc<Rec>>
+		I: Iterator<Item = &'a Arc<Rec>>
 	{
-		let mut ret = String::new();
+		let mut ret = Vec::<PkiLine>::new();
 		for req in reqs {
-			if let Some(line) = self.generate_pki_line(req, first_req_key) {
-				ret.push_str(&line);
+			let (line, reject) = self.generate_pki_line(req, first_req_key);
+			if let Some(line) = line {
+				ret.push(line);
+			}
+			if let Some(reject) = reject {
+				rejects.push(reject);
 			}
 		}
 		ret
 	}
 
-	/// 生成PKI格式輸出
-	/// 格式: |YYYYMMDD|BrkNo|Ivac(補0至7碼)|字元|FromUID(右補至15碼)|HHMMSS|digsgn
-	pub fn get_pki_output(&self) -> String {
-		let mut ret = String::new();
+	/// 生成所有 Req 的PKI記錄, 版面見 pki_layout
+	pub fn get_pki_records(&mut self) -> Vec<PkiLine> {
+		let mut ret = Vec::<PkiLine>::new();
+		let mut rejects = Vec::<PkiReject>::new();
 		for (req_key, req) in &self.ord_rec.reqs {
 			// 找到該 Req 對應訂單的第一筆 Req 作為備用值來源
 			let first_req_key = self.ord_rec.req2ord.get(req_key)
@@ -647,15 +1045,31 @@ impl Parser {
 					self.ord_rec.reqs.iter().find(|(k, _)| k.as_str() == first_req_key).map(|(_, r)| r)
 				});
 			
-			ret.push_str(&self.generate_pki_from_reqs(std::iter::once(req), first_req_key));
+			ret.extend(self.generate_pki_from_reqs(std::iter::once(req), first_req_key, &mut rejects));
 		}
+		self.pki_rejects.extend(rejects);
 		ret
 	}
 
+	/// 取出 find_by_conditions 產生的PKI記錄
+	pub fn take_pki_lines(&mut self) -> Vec<PkiLine> {
+		std::mem::take(&mut self.pki_lines)
+	}
+
+	/// 取出累積的PKI剔除記錄, 並標上來源檔名
+	pub fn take_pki_rejects(&mut self, source: &str) -> Vec<PkiReject> {
+		let mut rejects = std::mem::take(&mut self.pki_rejects);
+		for rej in &mut rejects {
+			rej.source = source.to_string();
+		}
+		rejects
+	}
+
 	/// 根據搜尋結果生成PKI格式輸出
 	/// list_of_list 是搜尋結果，包含所有符合條件的訂單
-	pub fn get_pki_output_from_search(&self, list_of_list: &LinkedList<LinkedList<Rc<Rec>>>) -> String {
-		let mut ret = String::new();
+	pub fn get_pki_output_from_search(&mut self, list_of_list: &LinkedList<LinkedList<Arc<Rec>>>) -> Vec<PkiLine> {
+		let mut ret = Vec::<PkiLine>::new();
+		let mut rejects = Vec::<PkiReject>::new();
 		for list in list_of_list {
 			// 找到該訂單的第一筆 Req 作為備用欄位來源
 			let first_req_key = list.iter()
@@ -663,37 +1077,95 @@ impl Parser {
 				.and_then(|rec| self.ord_rec.reqs.get(rec.get_field(1)));
 			
 			// 收集該訂單中的所有 Req 記錄
-			let req_list: Vec<&Rc<Rec>> = list.iter()
+			let req_list: Vec<&Arc<Rec>> = list.iter()
 				.filter(|rec| rec.get_field(0) == "Req")
 				.filter_map(|rec| self.ord_rec.reqs.get(rec.get_field(1)))
 				.collect();
 			
-			ret.push_str(&self.generate_pki_from_reqs(req_list.into_iter(), first_req_key));
+			ret.extend(self.generate_pki_from_reqs(req_list.into_iter(), first_req_key, &mut rejects));
 		}
+		self.pki_rejects.extend(rejects);
 		ret
 	}
 
 	/// 解析每一行的內容, 並儲存到HashMap
-	pub fn parse_line(&mut self, line: &str, log: &str, digsgn: &str) {
+	/// line_no 為該行在 log 檔中的行號
+	pub fn parse_line(&mut self, line: &str, log: &str, digsgn: &str, line_no: usize) {
 		let toks : Vec<String> = line.split('\x01').map(|s| s.to_string()).collect();
 
 		if toks.len() > 3 {
-			self.prevkey = self.ord_rec.insert_rec(toks, line, log, digsgn);
+			self.prevkey = self.ord_rec.insert_rec(toks, line, log, digsgn, line_no);
 		} else {
 			//println!("log line: {}", line);
 		}
 	}
 
 	/// 從輸入中解析出所有條件
+	/// pki_output 時不輸出明細，改為產生PKI記錄 (見 take_pki_lines)
 	/// 支持 , (AND/交集) 和 | (OR/聯集) 運算符
 	/// 例如: TwfNew:Side:B|TwfChg:Side:B (聯集：符合其中一個條件)
 	/// 例如: TwfNew:Side:B,TwfChg:Side:B (交集：同時符合兩個條件)
-	pub fn find_by_conditions(&mut self, condstr: &str, savefile: &str, hide: &bool, pki_output: bool, quiet: bool) {
-		let mut final_result: Option<LinkedList<LinkedList<Rc<Rec>>>> = None;
+	/// 條件格式錯誤時回傳說明，不會產生PKI記錄
+	pub fn find_by_conditions(&mut self, condstr: &str, savefile: &str, hide: &bool, pki_output: bool, quiet: bool) -> Result<(), String> {
+		let ret = self.search(condstr, hide, quiet)?;
+		if ret.is_empty() {
+			if !quiet {
+				println!("not found any matches");
+			}
+			return Ok(());
+		}
+		if !quiet {
+			println!("{} occurence found.", ret.len());
+		}
+		if pki_output {
+			// PKI 模式：產生符合條件的記錄的 PKI 格式，由呼叫端以 take_pki_lines 取出寫檔
+			self.pki_lines = self.get_pki_output_from_search(&ret);
+			if !quiet {
+				println!("{} PKI records generated.", self.pki_lines.len());
+			}
+		} else {
+			// 普通模式：輸出詳細資訊
+			if !hide {
+				self.print_orders(&ret, condstr);
+			}
+			self.save_to_file(&ret, savefile);
+		}
+		Ok(())
+	}
+
+	/// 印出搜尋到的委託，set_pretty 時以 欄位名 = 值 對齊列出並標示符合 condstr 的欄位
+	pub fn print_orders(&self, list_of_list: &LinkedList<LinkedList<Arc<Rec>>>, condstr: &str) {
+		let color = std::io::stdout().is_terminal();
+		for list in list_of_list {
+			if self.pretty {
+				print!("{}", self.ord_rec.pretty_ord_list(list, condstr, color));
+			} else {
+				self.ord_rec.print_ord_list(list);
+			}
+		}
+	}
+
+	/// 在記錄附加的 log 中全文搜尋 (不分大小寫)，回傳 log 含有 text 的記錄所屬的委託
+	pub fn search_log_text(&self, text: &str) -> LinkedList<LinkedList<Arc<Rec>>> {
+		let index = self.ord_rec.log_index();
+		self.ord_rec.owning_orders(index.find_text(text))
+	}
+
+	/// 在記錄附加的 log 中以正規表示式搜尋，回傳符合的記錄所屬的委託
+	pub fn search_log_regex(&self, pattern: &str) -> Result<LinkedList<LinkedList<Arc<Rec>>>, regex::Error> {
+		let re = regex::Regex::new(pattern)?;
+		let index = self.ord_rec.log_index();
+		Ok(self.ord_rec.owning_orders(index.find_regex(&re)))
+	}
+
+	/// 依條件搜尋，回傳符合的訂單 (每筆為該訂單的 ReqOrd list)，條件語法同 find_by_conditions
+	/// 沒有符合的訂單時回傳空的 list，條件格式錯誤時回傳說明
+	pub fn search(&self, condstr: &str, hide: &bool, quiet: bool) -> Result<LinkedList<LinkedList<Arc<Rec>>>, String> {
+		let mut final_result: Option<LinkedList<LinkedList<Arc<Rec>>>> = None;
 		
 		// 先按 ',' 分割交集條件組
 		for and_group in condstr.split(',') {
-			let mut or_result: LinkedList<LinkedList<Rc<Rec>>> = LinkedList::new();
+			let mut or_result: LinkedList<LinkedList<Arc<Rec>>> = LinkedList::new();
 			let mut seen_ords: HashSet<String> = HashSet::new();  // 用來去重（根據 Ord 記錄）
 			let mut has_result = false;
 			
@@ -701,7 +1173,12 @@ impl Parser {
 			for or_cond in and_group.split('|') {
 				let toks : Vec<&str> = or_cond.trim().split(':').collect();
 				if toks.len() > 2 {
-					if let Some(search_list) = self.ord_rec.check_req_data(toks[0], toks[1], toks[2], hide, quiet) {
+					let found = if toks[0] == LOG_TABLE {
+						Some(self.ord_rec.find_log_field(toks[1], toks[2]))
+					} else {
+						self.ord_rec.check_req_data(toks[0], toks[1], toks[2], hide, quiet)
+					};
+					if let Some(search_list) = found {
 						has_result = true;
 						// 將搜尋結果合併到 or_result（聯集操作），同時去重
 						for item in search_list {
@@ -723,9 +1200,9 @@ impl Parser {
 							}
 						}
 					}
-				} else if !quiet {
-						println!("{} is not correct! please specify TableName:FieldName:Value", or_cond);
-					}
+				} else {
+					return Err(format!("{} is not correct! please specify TableName:FieldName:Value", or_cond));
+				}
 			}
 			
 			// 不同的 and_group 是交集 (AND)
@@ -733,7 +1210,7 @@ impl Parser {
 				final_result = match final_result {
 					Some(lol1) => {
 						// 對 lol1 進行過濾，只保留同時符合 or_result 中任何條件的項目
-						let mut filtered = LinkedList::<LinkedList<Rc<Rec>>>::new();
+						let mut filtered = LinkedList::<LinkedList<Arc<Rec>>>::new();
 						for item1 in lol1 {
 							for item2 in &or_result {
 								// 比較兩個 list 中的 req key
@@ -766,53 +1243,11 @@ impl Parser {
 			}
 		}
 		
-		match final_result {
-			Some(ret) => {
-				if !quiet {
-					println!("{} occurence found.", ret.len());
-				}
-				if pki_output {
-					// PKI 模式：輸出符合條件的記錄的 PKI 格式
-					let pki_str = self.get_pki_output_from_search(&ret);
-					if !pki_str.is_empty() {
-						// 若沒有指定檔案名，自動產生
-						let output_file = if savefile.is_empty() {
-							let now = chrono::Local::now();
-							format!("PKILog-{}.log", now.format("%Y%m%d"))
-						} else {
-							savefile.to_string()
-						};
-						
-						if let Ok(mut file) = OpenOptions::new()
-							.create(true)
-							.append(true)
-							.open(&output_file) {
-							let _ = file.write_all(pki_str.as_bytes());
-							if !quiet {
-								println!("PKI output saved to: {}", output_file);
-							}
-						}
-					}
-				} else {
-					// 普通模式：輸出詳細資訊
-					if !hide {
-						for list in &ret {
-							self.ord_rec.print_ord_list(list);
-						}
-					}
-					self.save_to_file(&ret, savefile);
-				}
-			},
-			None => {
-				if !quiet {
-					println!("not found any matches");
-				}
-			}
-		};
+		Ok(final_result.unwrap_or_default())
 	}
 
 	/// 把list of list 存到檔案
-	pub fn save_to_file(&self, list_of_list: &LinkedList<LinkedList<Rc<Rec>>>, savefile: &str) {
+	pub fn save_to_file(&self, list_of_list: &LinkedList<LinkedList<Arc<Rec>>>, savefile: &str) {
 		if let Ok(mut buff) = File::create(savefile) {
 			for list in list_of_list {
 				let _ = buff.write(self.ord_rec.ord_list_to_string(list).as_bytes());
@@ -840,4 +1275,58 @@ impl fmt::Display for Parser {
 		write!(f, "tables: {} reqs: {} ords:{}", 
 			self.ord_rec.tables.len(), self.ord_rec.reqs.len(), self.ord_rec.ords.len())
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	use super::*;
+
+	/// 測試記錄用的表格定義，佔第 1 到 4 行
+	const TABLES: [&str; 4] = [
+		"Req|-|TwfNew|Time|ReqKind|SesName|BrkNo|IvacNo|FromUID|User|SorRID|Symbol|Side|Pri|Qty",
+		"Req|-|TwfChg|Time|ReqKind|SesName|User|SorRID|Qty",
+		"Req|-|TwfDeal|Time|ReqKind|User|DealQty|DealPri",
+		"Ord|-|TwfOrd|Time|ReqKey|OrdNo|OrderSt|ReqStep|LeavesQty|ErrMsg",
+	];
+
+	/// 先解析 TABLES，再解析以 | 分隔欄位的記錄行，記錄的行號從 5 開始
+	pub(crate) fn parse<S: AsRef<str>>(lines: &[S]) -> Parser {
+		let mut parser = Parser::new();
+		let all = TABLES.iter().copied().chain(lines.iter().map(|line| line.as_ref()));
+		for (idx, line) in all.enumerate() {
+			parser.parse_line(&line.replace('|', "\x01"), "", "", idx + 1);
+		}
+		parser
+	}
+
+	#[test]
+	fn search_finds_req_without_ord() {
+		let parser = parse(&[
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Ord|O1|TwfOrd|1704157201.000002|R1|A0001|101|7|0|",
+			"Req|R2|TwfNew|1704157210.000001|1|SorAPI|9A95|456|uid2|u2|100002|2317|S|100|2000",
+		]);
+		let found = parser.search("TwfNew:User:u2", &true, true).unwrap();
+		assert_eq!(found.len(), 1);
+		let keys: Vec<&str> = found.front().unwrap().iter().map(|rec| rec.get_key()).collect();
+		assert_eq!(keys, ["R2"]);
+
+		let found = parser.search("TwfNew:Symbol:2330", &true, true).unwrap();
+		let keys: Vec<&str> = found.front().unwrap().iter().map(|rec| rec.get_key()).collect();
+		assert_eq!(keys, ["R1", "O1"]);
+	}
+
+	#[test]
+	fn failed_counts_agree() {
+		let parser = parse(&[
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Ord|O1|TwfOrd|1704157201.000002|R1|A0001|99|6|0|",
+			"Req|R2|TwfNew|1704157202.000001|1|SorAPI|9A95|123|uid1|u1|100002|2330|B|600|1000",
+			"Ord|O2|TwfOrd|1704157203.000002|R2|A0002|7|99|0|",
+			"Req|R3|TwfNew|1704157204.000001|1|SorAPI|9A95|123|uid1|u1|100003|2330|B|600|1000",
+			"Ord|O3|TwfOrd|1704157205.000002|R3|A0003|101|7|0|",
+		]);
+		assert_eq!(parser.get_counts().invalid, 2);
+		assert_eq!(parser.count_failed_by_table(), [("TwfOrd".to_string(), 2)]);
+	}
+}
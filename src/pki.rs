@@ -0,0 +1,751 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufReader, Error, ErrorKind, Read, Result, Write};
+use std::path::Path;
+use chrono::Local;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::codes;
+
+// PKI 記錄的版面設定
+// 預設值即為原本寫死的格式:
+// |YYYYMMDD|BrkNo|Ivac(補0到7碼)|O/C/M|FromUID(右靠15碼)|HHMMSS|digsgn(補足4096字元)
+
+/// 欄位值的來源: 以 '@' 開頭的是特殊來源，以 log: 開頭的是 log 欄位，其餘視為 TableRec 的欄位名稱
+pub const SRC_DATE  : &str = "@date";   // Req 的日期 YYYYMMDD
+pub const SRC_TIME  : &str = "@time";   // Req 的時間 HHMMSS
+pub const SRC_KIND  : &str = "@kind";   // ReqKind 經 kinds 對照後的字元
+pub const SRC_DIGSGN: &str = "@digsgn"; // ':' 開頭行的最後欄位(簽章)
+pub const SRC_LOG_PREFIX: &str = "log:"; // log:Tag 為 ':' 開頭行中 Tag=Value 的值
+
+/// 補字元的方向
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PadSide {
+	Left,  // 靠右對齊，左邊補字元
+	Right, // 靠左對齊，右邊補字元
+	None,  // 不補
+}
+
+/// PKI 記錄中的一個欄位
+#[derive(Deserialize, Clone, Debug)]
+pub struct PkiField {
+	/// 欄位名稱，作為表頭使用
+	pub name    : String,
+	/// 值的來源，見 SRC_* 常數
+	pub source  : String,
+	/// 補齊後的最小寬度，0 表示不補
+	#[serde(default)]
+	pub width   : usize,
+	#[serde(default = "default_pad")]
+	pub pad     : PadSide,
+	#[serde(default = "default_pad_char")]
+	pub pad_char: char,
+	/// 值為空時，是否從該訂單的第一筆 Req 取值
+	#[serde(default)]
+	pub fallback: bool,
+	/// 是否為必填欄位
+	#[serde(default = "default_required")]
+	pub required: bool,
+	/// 補齊前的最大長度，0 表示不限制
+	#[serde(default)]
+	pub max_len : usize,
+	/// 允許的內容 (regex)，空字串表示不檢查
+	#[serde(default)]
+	pub allowed : String,
+	#[serde(skip)]
+	allowed_re  : Option<Regex>,
+}
+
+fn default_pad() -> PadSide {
+	PadSide::None
+}
+
+fn default_pad_char() -> char {
+	' '
+}
+
+fn default_required() -> bool {
+	true
+}
+
+impl PkiField {
+	fn new(name: &str, source: &str, width: usize, pad: PadSide, pad_char: char, fallback: bool) -> PkiField {
+		PkiField {
+			name: name.to_string(),
+			source: source.to_string(),
+			width,
+			pad,
+			pad_char,
+			fallback,
+			required: true,
+			max_len: 0,
+			allowed: String::new(),
+			allowed_re: None,
+		}
+	}
+	fn with_check(mut self, max_len: usize, allowed: &str) -> PkiField {
+		self.max_len = max_len;
+		self.allowed = allowed.to_string();
+		self
+	}
+	/// 檢查補齊前的值，回傳不合格的原因
+	pub fn validate(&self, value: &str, separator: &str) -> Option<String> {
+		if value.is_empty() {
+			if self.required {
+				return Some(format!("{} is empty", self.name));
+			}
+			return None;
+		}
+		let len = value.chars().count();
+		if self.max_len > 0 && len > self.max_len {
+			return Some(format!("{} too long ({} > {})", self.name, len, self.max_len));
+		}
+		if !separator.is_empty() && value.contains(separator) {
+			return Some(format!("{} contains separator '{}'", self.name, separator));
+		}
+		if let Some(re) = &self.allowed_re {
+			if !re.is_match(value) {
+				return Some(format!("{} has invalid characters: {}", self.name, value));
+			}
+		}
+		None
+	}
+	/// 去除補齊的字元，還原成原本的值
+	pub fn unpad_value<'a>(&self, value: &'a str) -> &'a str {
+		match self.pad {
+			PadSide::Left  => value.trim_start_matches(self.pad_char),
+			PadSide::Right => value.trim_end_matches(self.pad_char),
+			PadSide::None  => value,
+		}
+	}
+	/// 依設定的寬度與方向補齊
+	pub fn pad_value(&self, value: &str) -> String {
+		let len = value.chars().count();
+		if self.pad == PadSide::None || len >= self.width {
+			return value.to_string();
+		}
+		let fill: String = std::iter::repeat_n(self.pad_char, self.width - len).collect();
+		match self.pad {
+			PadSide::Left => fill + value,
+			_ => value.to_string() + &fill,
+		}
+	}
+}
+
+/// PKI 記錄的版面: 欄位列表、分隔字元、ReqKind 對照
+#[derive(Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct PkiLayout {
+	/// 欄位間的分隔字串
+	pub separator         : String,
+	/// 行首是否加上分隔字串
+	pub leading_separator : bool,
+	/// 行尾是否加上分隔字串
+	pub trailing_separator: bool,
+	/// 只輸出此欄位等於 filter_value 的 Req, 空字串表示不過濾
+	pub filter_field      : String,
+	pub filter_value      : String,
+	/// ReqKind 對應的輸出字元，未列出的 ReqKind 不輸出並記錄為剔除
+	/// 預設為代碼對照表中 ReqKind 的 pki (見 codes)
+	pub kinds             : HashMap<String, String>,
+	/// 個別表格的 ReqKind 對應字元，優先於 kinds
+	/// 預設為代碼對照表中 [tables.<表名>.ReqKind] 的 pki
+	pub table_kinds       : HashMap<String, HashMap<String, String>>,
+	/// 不需輸出也不需記錄的 ReqKind
+	/// 預設為代碼對照表 [fills] 的成交回報 deal_kinds
+	pub ignore_kinds      : Vec<String>,
+	pub fields            : Vec<PkiField>,
+}
+
+impl Default for PkiLayout {
+	fn default() -> PkiLayout {
+		let mut layout = PkiLayout {
+			separator: "|".to_string(),
+			leading_separator: true,
+			trailing_separator: false,
+			filter_field: "SesName".to_string(),
+			filter_value: "SorAPI".to_string(),
+			kinds: codes::book().pki_kinds(),
+			table_kinds: codes::book().pki_table_kinds(),
+			ignore_kinds: codes::book().fills.deal_kinds.clone(),
+			fields: vec![
+				PkiField::new("Date", SRC_DATE, 0, PadSide::None, ' ', false).with_check(8, "^[0-9]{8}$"),
+				PkiField::new("BrkNo", "BrkNo", 0, PadSide::None, ' ', true).with_check(0, "^[0-9A-Za-z]+$"),
+				PkiField::new("Ivac", "IvacNo", 7, PadSide::Left, '0', true).with_check(7, "^[0-9]+$"),
+				PkiField::new("Type", SRC_KIND, 0, PadSide::None, ' ', false),
+				PkiField::new("FromUID", "FromUID", 15, PadSide::Left, ' ', false).with_check(15, "^[\\x21-\\x7E]+$"),
+				PkiField::new("Time", SRC_TIME, 0, PadSide::None, ' ', false).with_check(6, "^[0-9]{6}$"),
+				PkiField::new("DigsgnHash", SRC_DIGSGN, 4096, PadSide::Right, ' ', false).with_check(4096, "^[\\x21-\\x7E]+$"),
+			],
+		};
+		// 內建的 regex 必定合法
+		let _ = layout.compile();
+		layout
+	}
+}
+
+impl PkiLayout {
+	/// 從 TOML 設定檔載入版面，未指定的項目使用預設值
+	/// 只指定 kinds 時不使用預設的 table_kinds，以免預設值優先於使用者的 kinds
+	pub fn load(path: &str) -> Result<PkiLayout> {
+		let content = fs::read_to_string(path)?;
+		let table: toml::Table = toml::from_str(&content)
+			.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+		let own_kinds = table.contains_key("kinds") && !table.contains_key("table_kinds");
+		let mut layout: PkiLayout = table.try_into()
+			.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+		if own_kinds {
+			layout.table_kinds.clear();
+		}
+		if layout.fields.is_empty() {
+			return Err(Error::new(ErrorKind::InvalidData, format!("{}: no fields defined", path)));
+		}
+		layout.compile()
+			.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))?;
+		Ok(layout)
+	}
+	/// 編譯各欄位的 allowed regex
+	fn compile(&mut self) -> std::result::Result<(), regex::Error> {
+		for field in &mut self.fields {
+			field.allowed_re = if field.allowed.is_empty() {
+				None
+			} else {
+				Some(Regex::new(&field.allowed)?)
+			};
+		}
+		Ok(())
+	}
+	/// 是否為不需處理的 ReqKind
+	pub fn is_ignored_kind(&self, req_kind: &str) -> bool {
+		self.ignore_kinds.iter().any(|k| k == req_kind)
+	}
+	/// 取得所有欄位名稱
+	pub fn headers(&self) -> Vec<String> {
+		self.fields.iter().map(|f| f.name.clone()).collect()
+	}
+	/// 以欄位名稱或來源 (可省略 '@') 找出欄位位置，不分大小寫
+	pub fn field_index(&self, name: &str) -> Option<usize> {
+		self.fields.iter().position(|f| f.name.eq_ignore_ascii_case(name))
+			.or_else(|| self.fields.iter().position(|f| f.source.trim_start_matches('@').eq_ignore_ascii_case(name.trim_start_matches('@'))))
+	}
+	/// 取得 @kind 欄位的位置
+	fn kind_index(&self) -> Option<usize> {
+		self.fields.iter().position(|f| f.source == SRC_KIND)
+	}
+	/// 所有可能輸出的種類字元 (排序後不重複)
+	fn kind_chars(&self) -> Vec<String> {
+		let mut chars: Vec<String> = self.kinds.values()
+			.chain(self.table_kinds.values().flat_map(|kinds| kinds.values()))
+			.cloned()
+			.collect();
+		chars.sort();
+		chars.dedup();
+		chars
+	}
+	/// 取得表格 table 的 ReqKind 對應的字元，先找該表格的，再找共用的
+	pub fn kind_char(&self, table: &str, req_kind: &str) -> Option<&str> {
+		self.table_kinds.get(table).and_then(|kinds| kinds.get(req_kind))
+			.or_else(|| self.kinds.get(req_kind))
+			.map(|s| s.as_str())
+	}
+	/// 將已補齊的欄位值組成一行 (不含換行)
+	pub fn format_line(&self, values: &[String]) -> String {
+		let mut line = String::new();
+		if self.leading_separator {
+			line.push_str(&self.separator);
+		}
+		line.push_str(&values.join(&self.separator));
+		if self.trailing_separator {
+			line.push_str(&self.separator);
+		}
+		line
+	}
+	/// 將一行 PKI 記錄拆回各欄位值 (未去除補齊字元)
+	pub fn split_line<'a>(&self, line: &'a str) -> Vec<&'a str> {
+		let mut body = line.trim_end_matches(['\r', '\n']);
+		if self.leading_separator {
+			body = body.strip_prefix(self.separator.as_str()).unwrap_or(body);
+		}
+		if self.trailing_separator {
+			body = body.strip_suffix(self.separator.as_str()).unwrap_or(body);
+		}
+		body.splitn(self.fields.len(), self.separator.as_str()).collect()
+	}
+}
+
+/// 一筆產生出來的 PKI 記錄及其來源 Req
+pub struct PkiLine {
+	pub req_key: String,
+	pub line   : String, // 不含換行
+}
+
+/// 從 PKI 檔讀回的一筆記錄
+#[derive(Clone)]
+pub struct PkiRecord {
+	pub line  : String,
+	pub values: Vec<String>, // 已去除補齊字元，順序同 PkiLayout::fields
+}
+
+impl PkiRecord {
+	/// 依版面解析一行 PKI 記錄，表頭表尾或欄位數不符時回傳 None
+	pub fn parse(layout: &PkiLayout, line: &str) -> Option<PkiRecord> {
+		if is_envelope_line(line) {
+			return None;
+		}
+		let parts = layout.split_line(line);
+		if parts.len() != layout.fields.len() {
+			return None;
+		}
+		let values = parts.iter().zip(&layout.fields).map(|(v, f)| f.unpad_value(v).to_string()).collect();
+		Some(PkiRecord { line: line.trim_end_matches(['\r', '\n']).to_string(), values })
+	}
+}
+
+/// 讀入 PKI 檔中所有記錄，無法解析的行回傳於第二個值
+pub fn load_records(path: &str, layout: &PkiLayout) -> Result<(Vec<PkiRecord>, Vec<String>)> {
+	let content = fs::read_to_string(path)?;
+	let mut records = Vec::<PkiRecord>::new();
+	let mut bad_lines = Vec::<String>::new();
+	for line in content.lines() {
+		if line.is_empty() || is_envelope_line(line) {
+			continue;
+		}
+		match PkiRecord::parse(layout, line) {
+			Some(rec) => records.push(rec),
+			None => bad_lines.push(line.to_string()),
+		}
+	}
+	Ok((records, bad_lines))
+}
+
+/// PKI 記錄的過濾條件: 以 ',' 連接的 欄位:值, 例如 Date:20240102,BrkNo:9A95,Type:O
+/// 同一欄位出現多次時為聯集
+pub struct PkiFilter {
+	conds: Vec<(usize, Vec<String>)>, // 欄位位置-可接受的值
+}
+
+impl PkiFilter {
+	pub fn parse(layout: &PkiLayout, filter: &str) -> std::result::Result<PkiFilter, String> {
+		let mut conds = Vec::<(usize, Vec<String>)>::new();
+		for cond in filter.split(',').map(|c| c.trim()).filter(|c| !c.is_empty()) {
+			let (name, value) = cond.split_once(':')
+				.ok_or_else(|| format!("{} is not correct! please specify FieldName:Value", cond))?;
+			let idx = layout.field_index(name)
+				.ok_or_else(|| format!("field {} not found", name))?;
+			let value = layout.fields[idx].unpad_value(value).to_string();
+			match conds.iter_mut().find(|(i, _)| *i == idx) {
+				Some((_, values)) => values.push(value),
+				None => conds.push((idx, vec![value])),
+			}
+		}
+		Ok(PkiFilter { conds })
+	}
+	pub fn matches(&self, rec: &PkiRecord) -> bool {
+		self.conds.iter().all(|(idx, values)| {
+			let v = rec.values.get(*idx).map(|s| s.as_str()).unwrap_or("");
+			values.iter().any(|t| t == v)
+		})
+	}
+}
+
+/// 被剔除或被標記的 PKI 候選記錄的嚴重程度
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum PkiSeverity {
+	Excluded, // 未輸出到 PKI 檔
+	Flagged,  // 已輸出，但內容需要確認
+}
+
+/// 一筆未通過檢查的 PKI 候選記錄
+#[derive(Clone, Debug)]
+pub struct PkiReject {
+	pub severity : PkiSeverity,
+	pub source   : String,   // 來源 SorReqOrd.log
+	pub req_key  : String,
+	pub table    : String,
+	pub timestamp: String,
+	pub reasons  : Vec<String>,
+}
+
+impl fmt::Display for PkiReject {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		let severity = match self.severity {
+			PkiSeverity::Excluded => "EXCLUDED",
+			PkiSeverity::Flagged  => "FLAGGED",
+		};
+		write!(f, "{}\t{}\t{}\t{}\t{}\t{}", severity, self.source, self.req_key, self.table, self.timestamp, self.reasons.join("; "))
+	}
+}
+
+/// 預設的 PKI 輸出檔名: PKILog-{今天日期}.log
+pub fn default_output_file() -> String {
+	format!("PKILog-{}.log", Local::now().format("%Y%m%d"))
+}
+
+/// 取得 PKI 輸出檔對應的剔除記錄檔名, 例如 PKILog-20240102.log => PKILog-20240102.rej
+pub fn rejects_path(output_file: &str) -> String {
+	format!("{}.rej", output_file.strip_suffix(".log").unwrap_or(output_file))
+}
+
+/// 將剔除記錄寫入檔案 (覆蓋前次內容)，回傳被剔除(未輸出)的筆數
+pub fn write_rejects(path: &str, rejects: &[PkiReject]) -> Result<usize> {
+	let mut file = File::create(path)?;
+	writeln!(file, "# severity\tsource\treq_key\ttable\ttimestamp\treasons")?;
+	for rej in rejects {
+		writeln!(file, "{}", rej)?;
+	}
+	Ok(count_excluded(rejects))
+}
+
+/// 計算被剔除(未輸出)的筆數
+pub fn count_excluded(rejects: &[PkiReject]) -> usize {
+	rejects.iter().filter(|r| r.severity == PkiSeverity::Excluded).count()
+}
+
+// PKI 檔的表頭與表尾記錄
+// #HDR|generated=YYYYMMDDHHMMSS|sources=dir1;dir2
+// #TRL|records=N|C=n|M=n|O=n|sha256=<本文的SHA-256>
+pub const HEADER_TAG : &str = "#HDR";
+pub const TRAILER_TAG: &str = "#TRL";
+
+/// 是否為表頭或表尾記錄
+pub fn is_envelope_line(line: &str) -> bool {
+	line.starts_with(HEADER_TAG) || line.starts_with(TRAILER_TAG)
+}
+
+/// 統計本文的筆數、各種類筆數及 SHA-256
+fn body_trailer(layout: &PkiLayout, body: &[&str]) -> String {
+	let mut hasher = Sha256::new();
+	let mut counts: Vec<(String, usize)> = layout.kind_chars().into_iter().map(|c| (c, 0)).collect();
+	let kind_idx = layout.kind_index();
+	for line in body {
+		hasher.update(line.as_bytes());
+		hasher.update(b"\n");
+		if let Some(idx) = kind_idx {
+			if let Some(kind) = layout.split_line(line).get(idx) {
+				if let Some(cnt) = counts.iter_mut().find(|(c, _)| c == kind.trim()) {
+					cnt.1 += 1;
+				}
+			}
+		}
+	}
+	let mut trailer = format!("{}|records={}", TRAILER_TAG, body.len());
+	for (kind, cnt) in counts {
+		trailer.push_str(&format!("|{}={}", kind, cnt));
+	}
+	trailer.push_str(&format!("|sha256={:x}", hasher.finalize()));
+	trailer
+}
+
+/// 為已寫好的 PKI 檔加上表頭與表尾，既有的表頭表尾會被取代
+pub fn write_envelope(path: &str, layout: &PkiLayout, sources: &[String]) -> Result<()> {
+	let content = fs::read_to_string(path)?;
+	let body: Vec<&str> = content.lines().filter(|l| !is_envelope_line(l)).collect();
+	let mut file = File::create(path)?;
+	writeln!(file, "{}|generated={}|sources={}", HEADER_TAG, Local::now().format("%Y%m%d%H%M%S"), sources.join(";"))?;
+	for line in &body {
+		writeln!(file, "{}", line)?;
+	}
+	writeln!(file, "{}", body_trailer(layout, &body))?;
+	Ok(())
+}
+
+/// 以表尾記錄檢查 PKI 檔是否完整，回傳發現的問題 (空的表示通過)
+pub fn verify(path: &str, layout: &PkiLayout) -> Result<Vec<String>> {
+	let content = fs::read_to_string(path)?;
+	let lines: Vec<&str> = content.lines().collect();
+	let mut problems = Vec::<String>::new();
+	if !lines.first().is_some_and(|l| l.starts_with(HEADER_TAG)) {
+		problems.push("header record missing".to_string());
+	}
+	let trailer = match lines.last() {
+		Some(l) if l.starts_with(TRAILER_TAG) => *l,
+		_ => {
+			problems.push("trailer record missing".to_string());
+			return Ok(problems);
+		}
+	};
+	let body: Vec<&str> = lines.iter().filter(|l| !is_envelope_line(l)).cloned().collect();
+	if lines.iter().filter(|l| is_envelope_line(l)).count() != 2 {
+		problems.push("misplaced header/trailer records".to_string());
+	}
+	let expected = body_trailer(layout, &body);
+	// 逐項比對，方便看出是哪一項不符
+	let actual_items: Vec<&str> = trailer.split('|').skip(1).collect();
+	let expected_items: Vec<&str> = expected.split('|').skip(1).collect();
+	for item in &expected_items {
+		let name = item.split('=').next().unwrap_or("");
+		match actual_items.iter().find(|a| a.split('=').next() == Some(name)) {
+			Some(actual) if actual == item => {},
+			Some(actual) => problems.push(format!("{} mismatch: trailer {}, content {}", name, actual, item)),
+			None => problems.push(format!("{} missing in trailer, content {}", name, item)),
+		}
+	}
+	Ok(problems)
+}
+
+// PKI 檔的執行紀錄 (manifest)
+// 記錄每次執行時讀了哪些來源檔 (路徑、大小、SHA-256)，以及每筆 PKI 記錄來自哪個來源的哪個 Req
+// 重新執行時只附加新的記錄，已存在的記錄不會重覆輸出
+
+/// 取得 PKI 輸出檔對應的 manifest 檔名, 例如 PKILog-20240102.log => PKILog-20240102.manifest.json
+pub fn manifest_path(output_file: &str) -> String {
+	format!("{}.manifest.json", output_file.strip_suffix(".log").unwrap_or(output_file))
+}
+
+/// 一次執行中讀取的來源檔
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ManifestSource {
+	pub path   : String,
+	pub size   : u64,
+	pub sha256 : String,
+	pub added  : usize, // 本次新增的記錄數
+	pub skipped: usize, // 已存在而略過的記錄數
+}
+
+/// 一次執行
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ManifestRun {
+	pub time   : String,
+	pub sources: Vec<ManifestSource>,
+}
+
+/// 一筆已寫入 PKI 檔的記錄
+#[derive(Serialize, Deserialize, Clone)]
+pub struct ManifestRecord {
+	pub source : String,
+	pub req_key: String,
+	pub sha256 : String, // 該行內容(不含換行)的 SHA-256
+	pub run    : usize,  // 寫入時的執行序號 (runs 的 index)
+}
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct PkiManifest {
+	pub output : String,
+	pub runs   : Vec<ManifestRun>,
+	pub records: Vec<ManifestRecord>,
+}
+
+impl PkiManifest {
+	pub fn load(path: &str) -> Result<PkiManifest> {
+		let content = fs::read_to_string(path)?;
+		serde_json::from_str(&content)
+			.map_err(|e| Error::new(ErrorKind::InvalidData, format!("{}: {}", path, e)))
+	}
+	pub fn save(&self, path: &str) -> Result<()> {
+		let content = serde_json::to_string_pretty(self)
+			.map_err(|e| Error::new(ErrorKind::InvalidData, e))?;
+		fs::write(path, content)
+	}
+	/// 曾經貢獻過記錄的所有來源檔
+	pub fn sources(&self) -> Vec<String> {
+		let mut sources: Vec<String> = Vec::new();
+		for rec in &self.records {
+			if !sources.contains(&rec.source) {
+				sources.push(rec.source.clone());
+			}
+		}
+		sources
+	}
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+	format!("{:x}", Sha256::digest(data))
+}
+
+/// 計算檔案的大小及 SHA-256
+fn file_digest(path: &str) -> Result<(u64, String)> {
+	let mut reader = BufReader::new(File::open(path)?);
+	let mut hasher = Sha256::new();
+	let mut buf = vec![0u8; 64 * 1024];
+	let mut size: u64 = 0;
+	loop {
+		let n = reader.read(&mut buf)?;
+		if n == 0 {
+			break;
+		}
+		hasher.update(&buf[..n]);
+		size += n as u64;
+	}
+	Ok((size, format!("{:x}", hasher.finalize())))
+}
+
+/// 以 manifest 去重後，將 PKI 記錄附加到輸出檔
+pub struct PkiWriter {
+	output       : String,
+	manifest_path: String,
+	manifest     : PkiManifest,
+	known_keys   : HashSet<(String, String)>, // (來源, req_key)
+	known_lines  : HashSet<String>,           // 已存在行的 SHA-256
+	run          : ManifestRun,
+	rejects      : Vec<PkiReject>,            // 本次執行被剔除或標記的記錄
+	enveloped    : bool,                      // 輸出檔原本已有表頭或表尾
+}
+
+impl PkiWriter {
+	/// 開啟輸出檔及其 manifest；輸出檔中既有的記錄即使不在 manifest 中也不會重覆寫入
+	pub fn open(output: &str) -> Result<PkiWriter> {
+		let manifest_path = manifest_path(output);
+		let mut manifest = if Path::new(&manifest_path).exists() {
+			PkiManifest::load(&manifest_path)?
+		} else {
+			PkiManifest::default()
+		};
+		manifest.output = output.to_string();
+		let known_keys = manifest.records.iter().map(|r| (r.source.clone(), r.req_key.clone())).collect();
+		let mut known_lines: HashSet<String> = manifest.records.iter().map(|r| r.sha256.clone()).collect();
+		let mut enveloped = false;
+		if let Ok(content) = fs::read_to_string(output) {
+			for line in content.lines() {
+				if is_envelope_line(line) {
+					enveloped = true;
+				} else {
+					known_lines.insert(sha256_hex(line.as_bytes()));
+				}
+			}
+		}
+		Ok(PkiWriter {
+			output: output.to_string(),
+			manifest_path,
+			manifest,
+			known_keys,
+			known_lines,
+			run: ManifestRun { time: Local::now().format("%Y%m%d%H%M%S").to_string(), sources: Vec::new() },
+			rejects: Vec::new(),
+			enveloped,
+		})
+	}
+	pub fn output(&self) -> &str {
+		&self.output
+	}
+	/// 輸出檔原本是否已有表頭或表尾；附加的記錄會在表尾之後，需重寫表頭表尾
+	pub fn has_envelope(&self) -> bool {
+		self.enveloped
+	}
+	pub fn add_rejects(&mut self, rejects: Vec<PkiReject>) {
+		self.rejects.extend(rejects);
+	}
+	pub fn rejects(&self) -> &[PkiReject] {
+		&self.rejects
+	}
+	/// 附加一個來源檔產生的 PKI 記錄，回傳新增的筆數
+	pub fn add_source(&mut self, source: &str, lines: Vec<PkiLine>) -> Result<usize> {
+		let source_path = fs::canonicalize(source)
+			.map(|p| p.to_string_lossy().to_string())
+			.unwrap_or_else(|_| source.to_string());
+		let (size, sha256) = file_digest(source)?;
+		let run_idx = self.manifest.runs.len();
+		let mut body = String::new();
+		let mut added = 0;
+		let mut skipped = 0;
+		for pki_line in lines {
+			let key = (source_path.clone(), pki_line.req_key);
+			let line_hash = sha256_hex(pki_line.line.as_bytes());
+			if self.known_keys.contains(&key) || self.known_lines.contains(&line_hash) {
+				skipped += 1;
+				continue;
+			}
+			body.push_str(&pki_line.line);
+			body.push('\n');
+			self.manifest.records.push(ManifestRecord { source: source_path.clone(), req_key: key.1.clone(), sha256: line_hash.clone(), run: run_idx });
+			self.known_keys.insert(key);
+			self.known_lines.insert(line_hash);
+			added += 1;
+		}
+		if !body.is_empty() {
+			let mut file = OpenOptions::new().create(true).append(true).open(&self.output)?;
+			file.write_all(body.as_bytes())?;
+		}
+		self.run.sources.push(ManifestSource { path: source_path, size, sha256, added, skipped });
+		Ok(added)
+	}
+	/// 記錄本次執行並寫回 manifest，回傳所有曾貢獻記錄的來源檔
+	pub fn finish(mut self) -> Result<Vec<String>> {
+		self.manifest.runs.push(self.run);
+		self.manifest.save(&self.manifest_path)?;
+		Ok(self.manifest.sources())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn pad_values() {
+		let ivac = PkiField::new("Ivac", "IvacNo", 7, PadSide::Left, '0', true);
+		assert_eq!(ivac.pad_value("123"), "0000123");
+		assert_eq!(ivac.pad_value("12345678"), "12345678");
+
+		let sign = PkiField::new("DigsgnHash", SRC_DIGSGN, 6, PadSide::Right, ' ', false);
+		assert_eq!(sign.pad_value("SIG"), "SIG   ");
+		// 寬度以字元計
+		assert_eq!(sign.pad_value("簽章"), "簽章    ");
+
+		let kind = PkiField::new("Type", SRC_KIND, 4, PadSide::None, ' ', false);
+		assert_eq!(kind.pad_value("O"), "O");
+	}
+
+	#[test]
+	fn layout_kinds_override_default_table_kinds() {
+		let path = std::env::temp_dir().join(format!("sor_logparser_layout_{}.toml", std::process::id()));
+		let path = path.to_str().unwrap();
+		let field = "[[fields]]\nname = \"Type\"\nsource = \"@kind\"\n";
+		fs::write(path, format!("[kinds]\n\"1\" = \"N\"\n{}", field)).unwrap();
+		let layout = PkiLayout::load(path).unwrap();
+		assert!(layout.table_kinds.is_empty());
+		assert_eq!(layout.kind_char("FrfNew", "1"), Some("N"));
+
+		fs::write(path, format!("[kinds]\n\"1\" = \"N\"\n[table_kinds.FrfNew]\n\"1\" = \"O\"\n{}", field)).unwrap();
+		let layout = PkiLayout::load(path).unwrap();
+		assert_eq!(layout.kind_char("FrfNew", "1"), Some("O"));
+		assert_eq!(layout.kind_char("TwfNew", "1"), Some("N"));
+		fs::remove_file(path).unwrap();
+	}
+
+	#[test]
+	fn envelope_verify_detects_changes() {
+		let path = std::env::temp_dir().join(format!("sor_logparser_envelope_{}.log", std::process::id()));
+		let path = path.to_str().unwrap();
+		let layout = PkiLayout::default();
+		let rec1 = "|20240102|9A95|0000123|O|           uid1|010000|SIGA";
+		let rec2 = "|20240102|9A95|0000123|M|           uid1|010005|SIGB";
+		fs::write(path, format!("{}\n{}\n", rec1, rec2)).unwrap();
+		assert_eq!(verify(path, &layout).unwrap(), ["header record missing", "trailer record missing"]);
+
+		write_envelope(path, &layout, &["/logs/20240102".to_string()]).unwrap();
+		assert!(verify(path, &layout).unwrap().is_empty());
+		let content = fs::read_to_string(path).unwrap();
+		assert!(content.starts_with("#HDR|generated="));
+		assert!(content.lines().last().unwrap().starts_with("#TRL|records=2|"));
+		assert!(PkiWriter::open(path).unwrap().has_envelope());
+
+		// 附加在表尾之後
+		fs::write(path, format!("{}{}\n", content, rec1.replace("SIGA", "SIGC"))).unwrap();
+		assert!(!verify(path, &layout).unwrap().is_empty());
+		// 重寫表頭表尾後通過
+		write_envelope(path, &layout, &[]).unwrap();
+		assert!(verify(path, &layout).unwrap().is_empty());
+
+		// 本文被修改
+		let content = fs::read_to_string(path).unwrap();
+		fs::write(path, content.replace("SIGB", "SIGX")).unwrap();
+		let problems = verify(path, &layout).unwrap();
+		assert_eq!(problems.len(), 1);
+		assert!(problems[0].starts_with("sha256 mismatch"));
+		let _ = fs::remove_file(path);
+	}
+
+	#[test]
+	fn unpad_values() {
+		let ivac = PkiField::new("Ivac", "IvacNo", 7, PadSide::Left, '0', true);
+		assert_eq!(ivac.unpad_value("0000123"), "123");
+		let sign = PkiField::new("DigsgnHash", SRC_DIGSGN, 6, PadSide::Right, ' ', false);
+		assert_eq!(sign.unpad_value("SIG   "), "SIG");
+		let kind = PkiField::new("Type", SRC_KIND, 4, PadSide::None, ' ', false);
+		assert_eq!(kind.unpad_value(" O "), " O ");
+	}
+}
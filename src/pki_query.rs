@@ -0,0 +1,140 @@
+use std::collections::{BTreeMap, HashSet};
+use std::path::Path;
+
+use crate::fileread::read_log_file;
+use crate::parser::Parser;
+use crate::pki::{self, PkiFilter, PkiLayout, PkiManifest, PkiRecord};
+
+// 讀回既有的 PKILog 檔: 過濾、統計，並與產生它們的 SorReqOrd.log 交叉比對
+
+/// 依某一欄位統計筆數
+fn count_by(records: &[PkiRecord], layout: &PkiLayout, name: &str) -> Option<String> {
+	let idx = layout.field_index(name)?;
+	let mut counts = BTreeMap::<&str, usize>::new();
+	for rec in records {
+		*counts.entry(rec.values[idx].as_str()).or_default() += 1;
+	}
+	let items: Vec<String> = counts.iter().map(|(k, v)| format!("{}={}", k, v)).collect();
+	Some(format!("by {}:\t{}\n", layout.fields[idx].name, items.join(", ")))
+}
+
+/// 找出 PKI 檔的來源 SorReqOrd.log: 先看 manifest，沒有的話掃描 scan_dir 的日期目錄
+fn find_sources(files: &[String], scan_dir: &str) -> Vec<String> {
+	let mut sources = Vec::<String>::new();
+	for file in files {
+		if let Ok(manifest) = PkiManifest::load(&pki::manifest_path(file)) {
+			for src in manifest.sources() {
+				if !sources.contains(&src) {
+					sources.push(src);
+				}
+			}
+		}
+	}
+	if sources.is_empty() {
+		if let Ok(dirs) = crate::find_date_directories(scan_dir) {
+			for dir in dirs {
+				let log_path = Path::new(&dir).join("SorReqOrd.log");
+				if log_path.exists() {
+					sources.push(log_path.to_string_lossy().to_string());
+				}
+			}
+		}
+	}
+	sources
+}
+
+/// 由來源檔重新產生符合過濾條件的PKI記錄
+fn regenerate(sources: &[String], encoding: &str, conditions: &str, layout: &PkiLayout, filter: &PkiFilter) -> Vec<PkiRecord> {
+	let mut records = Vec::<PkiRecord>::new();
+	for source in sources {
+		let mut parser = Parser::new();
+		parser.set_pki_layout(layout.clone());
+		if let Err(e) = read_log_file(Path::new(source), &mut parser, encoding) {
+			println!("error opening {}: {}", source, e);
+			continue;
+		}
+		let lines = if conditions.is_empty() {
+			parser.get_pki_records()
+		} else {
+			if let Err(e) = parser.find_by_conditions(conditions, "", &true, true, true) {
+				println!("{}: {}", source, e);
+				continue;
+			}
+			parser.take_pki_lines()
+		};
+		records.extend(lines.iter()
+			.filter_map(|l| PkiRecord::parse(layout, &l.line))
+			.filter(|r| filter.matches(r)));
+	}
+	records
+}
+
+/// 讀入 PKI 檔並依 filter 過濾、統計
+/// check: 是否與來源 SorReqOrd.log 交叉比對，conditions 為產生 PKI 檔時的搜尋條件
+/// 回傳是否沒有發現問題
+#[allow(clippy::too_many_arguments)]
+pub fn run(files: &[String], filter: &str, layout: &PkiLayout, hide: bool, check: bool, scan_dir: &str, encoding: &str, conditions: &str) -> bool {
+	let filter = match PkiFilter::parse(layout, filter) {
+		Ok(filter) => filter,
+		Err(e) => {
+			println!("{}", e);
+			return false;
+		}
+	};
+	let mut ok = true;
+	let mut records = Vec::<PkiRecord>::new();
+	for file in files {
+		match pki::load_records(file, layout) {
+			Ok((recs, bad_lines)) => {
+				if !bad_lines.is_empty() {
+					ok = false;
+					println!("{}: {} lines do not match the layout", file, bad_lines.len());
+					for line in bad_lines {
+						println!("  {}", line);
+					}
+				}
+				records.extend(recs.into_iter().filter(|r| filter.matches(r)));
+			},
+			Err(e) => {
+				println!("error opening {}: {}", file, e);
+				ok = false;
+			}
+		}
+	}
+
+	if !hide {
+		for rec in &records {
+			println!("{}", rec.line.trim_end());
+		}
+	}
+	println!("records:\t{}", records.len());
+	for name in ["kind", "date", "BrkNo"] {
+		if let Some(line) = count_by(&records, layout, name) {
+			print!("{}", line);
+		}
+	}
+
+	if check {
+		let sources = find_sources(files, scan_dir);
+		if sources.is_empty() {
+			println!("no source SorReqOrd.log found to cross-check");
+			return false;
+		}
+		println!("cross-checking with {} source logs", sources.len());
+		let generated = regenerate(&sources, encoding, conditions, layout, &filter);
+		let in_files: HashSet<&str> = records.iter().map(|r| r.line.as_str()).collect();
+		let in_sources: HashSet<&str> = generated.iter().map(|r| r.line.as_str()).collect();
+		let missing: Vec<&str> = generated.iter().map(|r| r.line.as_str()).filter(|l| !in_files.contains(l)).collect();
+		let unknown: Vec<&str> = records.iter().map(|r| r.line.as_str()).filter(|l| !in_sources.contains(l)).collect();
+		println!("missing in PKI files:\t{}", missing.len());
+		for line in &missing {
+			println!("  {}", line.trim_end());
+		}
+		println!("not found in sources:\t{}", unknown.len());
+		for line in &unknown {
+			println!("  {}", line.trim_end());
+		}
+		ok = ok && missing.is_empty() && unknown.is_empty();
+	}
+	ok
+}
@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::{Result, Write};
+use std::path::Path;
+
+use serde_json::{json, Value};
+
+use crate::codes;
+use crate::fileread::read_log_file;
+use crate::fills::{deal_fill, is_deal, new_order_req, split_order};
+use crate::parser::{OrderRec, Parser, Rec};
+use crate::util::csv_field;
+
+// 帳戶部位: 依券商代號 (BrkNo)、帳號 (IvacNo)、商品 (Symbol) 統計每天的買賣成交量、淨部位、成交金額及委託數
+// 成交回報沒有商品及買賣別，取自其委託的新單
+
+/// 一個帳戶在一個商品的統計
+#[derive(Default, Clone)]
+pub struct Position {
+	pub bought  : i64,
+	pub sold    : i64,
+	pub turnover: f64,   // 買賣成交金額合計
+	pub orders  : usize, // 委託數 (依新單的日期)
+	pub deals   : usize,
+}
+
+impl Position {
+	/// 淨部位: 買進減賣出
+	pub fn net(&self) -> i64 {
+		self.bought - self.sold
+	}
+	fn add(&mut self, other: &Position) {
+		self.bought += other.bought;
+		self.sold += other.sold;
+		self.turnover += other.turnover;
+		self.orders += other.orders;
+		self.deals += other.deals;
+	}
+}
+
+/// (BrkNo, IvacNo, Symbol)
+pub type PositionKey = (String, String, String);
+
+/// 各日期的帳戶部位
+#[derive(Default)]
+pub struct PositionReport {
+	pub days: BTreeMap<String, BTreeMap<PositionKey, Position>>,
+}
+
+/// 欄位值，空白時取自委託的新單
+fn value_or(ord_rec: &OrderRec, rec: &Rec, fallback: &Rec, name: &str) -> String {
+	let val = ord_rec.get_value(rec, name);
+	if val.is_empty() { ord_rec.get_value(fallback, name) } else { val }
+}
+
+impl PositionReport {
+	/// 加入一個 log 的所有委託，成交依成交回報的日期、委託數依新單的日期歸到各天
+	pub fn add_log(&mut self, ord_rec: &OrderRec) {
+		let codes = &codes::book().fills;
+		for key in ord_rec.ords.keys() {
+			let list = ord_rec.get_target_ordlist(key);
+			let (reqs, _) = split_order(&list);
+			let new_req = match new_order_req(ord_rec, &reqs) {
+				Some(req) => req,
+				None => continue,
+			};
+			let symbol = ord_rec.get_value(new_req, &codes.symbol_field);
+			let side = ord_rec.get_value(new_req, &codes.side_field);
+			let account = |rec| (value_or(ord_rec, rec, new_req, "BrkNo"), value_or(ord_rec, rec, new_req, "IvacNo"), symbol.clone());
+
+			let (brk, ivac, symbol) = account(new_req);
+			self.entry(new_req.get_date(), (brk, ivac, symbol)).orders += 1;
+			for deal in reqs.iter().filter(|rec| is_deal(ord_rec, rec)) {
+				let (qty, price) = deal_fill(ord_rec, deal);
+				let pos = self.entry(deal.get_date(), account(deal));
+				if side == codes.buy_side {
+					pos.bought += qty as i64;
+				} else if side == codes.sell_side {
+					pos.sold += qty as i64;
+				}
+				pos.turnover += qty * price;
+				pos.deals += 1;
+			}
+		}
+	}
+	fn entry(&mut self, date: String, key: PositionKey) -> &mut Position {
+		self.days.entry(date).or_default().entry(key).or_default()
+	}
+	/// 所有日期合計
+	pub fn totals(&self) -> BTreeMap<PositionKey, Position> {
+		let mut totals = BTreeMap::<PositionKey, Position>::new();
+		for positions in self.days.values() {
+			for (key, pos) in positions {
+				totals.entry(key.clone()).or_default().add(pos);
+			}
+		}
+		totals
+	}
+	/// 每天的部位；多於一天時再加上合計 (日期為 all)
+	fn rows(&self) -> Vec<(String, PositionKey, Position)> {
+		let mut rows: Vec<(String, PositionKey, Position)> = self.days.iter()
+			.flat_map(|(date, positions)| positions.iter().map(move |(key, pos)| (date.clone(), key.clone(), pos.clone())))
+			.collect();
+		if self.days.len() > 1 {
+			rows.extend(self.totals().into_iter().map(|(key, pos)| ("all".to_string(), key, pos)));
+		}
+		rows
+	}
+	/// 文字報表
+	pub fn format(&self) -> String {
+		let mut ret = String::from("Date\tBrkNo\tIvacNo\tSymbol\tBought\tSold\tNet\tTurnover\tOrders\tDeals\n");
+		for (date, (brk, ivac, symbol), pos) in self.rows() {
+			ret.push_str(&format!("{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:.2}\t{}\t{}\n",
+				date, brk, ivac, symbol, pos.bought, pos.sold, pos.net(), pos.turnover, pos.orders, pos.deals));
+		}
+		ret
+	}
+	/// 寫成 CSV，合計列的日期為 all
+	pub fn write_csv(&self, path: &str) -> Result<()> {
+		let mut file = fs::File::create(path)?;
+		writeln!(file, "Date,BrkNo,IvacNo,Symbol,Bought,Sold,Net,Turnover,Orders,Deals")?;
+		for (date, (brk, ivac, symbol), pos) in self.rows() {
+			writeln!(file, "{},{},{},{},{},{},{},{:.2},{},{}",
+				date, csv_field(&brk), csv_field(&ivac), csv_field(&symbol), pos.bought, pos.sold, pos.net(), pos.turnover, pos.orders, pos.deals)?;
+		}
+		Ok(())
+	}
+	/// JSON: days 為每天的部位，total 為所有日期合計
+	pub fn to_json(&self) -> Value {
+		let to_json = |positions: &BTreeMap<PositionKey, Position>| -> Vec<Value> {
+			positions.iter().map(|((brk, ivac, symbol), pos)| json!({
+				"brkno"   : brk,
+				"ivacno"  : ivac,
+				"symbol"  : symbol,
+				"bought"  : pos.bought,
+				"sold"    : pos.sold,
+				"net"     : pos.net(),
+				"turnover": pos.turnover,
+				"orders"  : pos.orders,
+				"deals"   : pos.deals,
+			})).collect()
+		};
+		let days: Vec<Value> = self.days.iter().map(|(date, positions)| json!({ "date": date, "positions": to_json(positions) })).collect();
+		json!({ "days": days, "total": to_json(&self.totals()) })
+	}
+	pub fn write_json(&self, path: &str) -> Result<()> {
+		fs::write(path, serde_json::to_string_pretty(&self.to_json()).map_err(std::io::Error::other)?)
+	}
+	/// 依副檔名寫成 JSON (.json) 或 CSV
+	pub fn save(&self, path: &str) -> Result<()> {
+		if path.to_lowercase().ends_with(".json") {
+			self.write_json(path)
+		} else {
+			self.write_csv(path)
+		}
+	}
+}
+
+/// 解析 filepath，未指定時解析 scan_dir 下各日期目錄的 SorReqOrd.log，印出部位報表，指定 savepath 時另存檔
+pub fn run(filepath: Option<&str>, scan_dir: &str, encoding: &str, savepath: &str) {
+	let logs: Vec<String> = match filepath {
+		Some(path) => vec![path.to_string()],
+		None => match crate::find_date_logs(scan_dir) {
+			Ok(logs) => logs,
+			Err(e) => {
+				println!("Error reading directory {}: {}", scan_dir, e);
+				return;
+			}
+		},
+	};
+	if logs.is_empty() {
+		println!("No SorReqOrd.log files found in date directories");
+		return;
+	}
+
+	let mut report = PositionReport::default();
+	for log in &logs {
+		println!("Processing: {}", log);
+		let mut parser = Parser::new();
+		match read_log_file(Path::new(log), &mut parser, encoding) {
+			Ok(()) => report.add_log(&parser.ord_rec),
+			Err(e) => println!("error opening {}: {}", log, e),
+		}
+	}
+	print!("{}", report.format());
+	if !savepath.is_empty() {
+		match report.save(savepath) {
+			Ok(()) => println!("positions saved to {}", savepath),
+			Err(e) => println!("Error writing {}: {}", savepath, e),
+		}
+	}
+}
@@ -0,0 +1,185 @@
+use std::collections::{BTreeMap, HashSet};
+use std::sync::Arc;
+
+use serde_json::{json, Value};
+
+use crate::codes;
+use crate::fills::{new_order_req, split_order};
+use crate::parser::{OrderRec, Rec};
+
+// 失敗委託: 列出 OrderSt 或 ReqStep 為委託失敗 (99) 的要求，及從 Ord 欄位或附加的 log 取出的失敗原因
+// 依原因統計筆數，方便找出大量重複的失敗
+
+/// 名稱含有這些字 (不分大小寫) 的 Ord 欄位或 log 欄位視為失敗原因
+const REASON_HINTS: [&str; 6] = ["err", "msg", "reason", "text", "rej", "desc"];
+
+/// 沒有找到失敗原因時的說明
+const NO_REASON: &str = "(no reason)";
+
+/// 一筆失敗的要求
+pub struct Rejected {
+	pub ord_key: String,
+	pub req_key: String,
+	pub user   : String,
+	pub table  : String,  // 要求的表名
+	pub kind   : String,  // 要求種類 (ReqKind)
+	pub ord    : Arc<Rec>, // 回報失敗的 Ord
+	pub reason : String,
+}
+
+impl Rejected {
+	/// 要求種類及說明，例如 1 新單
+	pub fn kind_label(&self) -> String {
+		format!("{} {}", self.kind, codes::label(&self.table, "ReqKind", &self.kind))
+	}
+}
+
+fn is_reason_name(name: &str) -> bool {
+	let name = name.to_lowercase();
+	REASON_HINTS.iter().any(|hint| name.contains(hint))
+}
+
+/// 失敗原因: 先找 Ord 的欄位，再找 Ord 及 Req 附加的 log 欄位，最後取 log 的第一行
+fn reject_reason(ord_rec: &OrderRec, ord: &Rec, req: Option<&Arc<Rec>>) -> String {
+	let from_fields = ord_rec.get_fields(ord).into_iter()
+		.find(|(name, value)| is_reason_name(name) && !value.trim().is_empty())
+		.map(|(_, value)| value);
+	let recs: Vec<&Rec> = std::iter::once(ord).chain(req.map(|req| req.as_ref())).collect();
+	let from_log_fields = || recs.iter()
+		.flat_map(|rec| rec.get_log_fields())
+		.find(|(tag, value)| is_reason_name(tag) && !value.trim().is_empty())
+		.map(|(_, value)| value);
+	let from_log_line = || ord.get_log_lines().into_iter().next();
+	from_fields.or_else(from_log_fields).or_else(from_log_line)
+		.map(|reason| reason.trim().to_string())
+		.unwrap_or_else(|| NO_REASON.to_string())
+}
+
+/// 所有失敗的要求，同一個要求只列第一筆失敗的 Ord，依時間排序
+pub fn rejected_orders(ord_rec: &OrderRec) -> Vec<Rejected> {
+	let mut ret = Vec::new();
+	for ord_key in ord_rec.ords.keys() {
+		let list = ord_rec.get_target_ordlist(ord_key);
+		let (reqs, ords) = split_order(&list);
+		let new_req = new_order_req(ord_rec, &reqs);
+		let mut seen = HashSet::<String>::new();
+		for ord in ords {
+			if !ord_rec.is_failed(ord) {
+				continue;
+			}
+			let req_key = ord.get_req_key().to_string();
+			if !seen.insert(req_key.clone()) {
+				continue;
+			}
+			let req = ord_rec.reqs.get(&req_key).or(new_req);
+			let value = |name: &str| req.map(|req| ord_rec.get_value(req, name)).unwrap_or_default();
+			ret.push(Rejected {
+				ord_key: ord_key.clone(),
+				req_key,
+				user   : value("User"),
+				table  : req.map(|req| req.get_table().to_string()).unwrap_or_default(),
+				kind   : value("ReqKind"),
+				ord    : Arc::clone(ord),
+				reason : reject_reason(ord_rec, ord, req),
+			});
+		}
+	}
+	ret.sort_by(|a, b| a.ord.get_secs().unwrap_or(0.0).total_cmp(&b.ord.get_secs().unwrap_or(0.0)).then(a.ord_key.cmp(&b.ord_key)));
+	ret
+}
+
+/// 依原因分組，筆數多的在前
+pub fn group_by_reason(rejected: &[Rejected]) -> Vec<(&str, Vec<&Rejected>)> {
+	let mut groups = BTreeMap::<&str, Vec<&Rejected>>::new();
+	for rej in rejected {
+		groups.entry(rej.reason.as_str()).or_default().push(rej);
+	}
+	let mut ret: Vec<(&str, Vec<&Rejected>)> = groups.into_iter().collect();
+	ret.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(b.0)));
+	ret
+}
+
+/// 失敗委託的文字報表，依原因分組
+pub fn format_report(ord_rec: &OrderRec) -> String {
+	let rejected = rejected_orders(ord_rec);
+	let groups = group_by_reason(&rejected);
+	let mut ret = format!("{} rejected requests, {} reasons\n", rejected.len(), groups.len());
+	for (reason, list) in groups {
+		ret.push_str(&format!("{}: {}\n", reason, list.len()));
+		for rej in list {
+			ret.push_str(&format!("\t{}\t{}\t{}\t{}\tOrdKey {}\tReqKey {}\t(line {})\n",
+				rej.ord.get_timestamp(), rej.user, rej.table, rej.kind_label(), rej.ord_key, rej.req_key, rej.ord.get_line_no()));
+		}
+	}
+	ret
+}
+
+/// 失敗委託的 JSON，依原因分組
+pub fn report_json(rejected: &[Rejected]) -> Value {
+	let reasons: Vec<Value> = group_by_reason(rejected).into_iter().map(|(reason, list)| json!({
+		"reason" : reason,
+		"count"  : list.len(),
+		"orders" : list.iter().map(|rej| json!({
+			"time"   : rej.ord.get_timestamp(),
+			"user"   : rej.user,
+			"table"  : rej.table,
+			"kind"   : rej.kind,
+			"label"  : codes::label(&rej.table, "ReqKind", &rej.kind),
+			"ord_key": rej.ord_key,
+			"req_key": rej.req_key,
+			"line_no": rej.ord.get_line_no(),
+		})).collect::<Vec<_>>(),
+	})).collect();
+	json!({ "count": rejected.len(), "reasons": reasons })
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::parser::tests::parse;
+
+	#[test]
+	fn rejects_grouped_by_reason() {
+		let parser = parse(&[
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Ord|O1|TwfOrd|1704157200.000002|R1|A0001|99|99|0|Insufficient margin",
+			"Ord|O1|TwfOrd|1704157200.000003|R1|A0001|99|99|0|Insufficient margin",
+			"Req|R2|TwfNew|1704157201.000001|1|SorAPI|9A95|123|uid2|u2|100002|2330|B|600|1000",
+			"Ord|O2|TwfOrd|1704157201.000002|R2|A0002|99|99|0| Insufficient margin ",
+			"Req|R3|TwfNew|1704157202.000001|1|SorAPI|9A95|123|uid3|u3|100003|2330|B|600|1000",
+			"Ord|O3|TwfOrd|1704157202.000002|R3|A0003|0|99|0|",
+			"Req|R4|TwfNew|1704157203.000001|1|SorAPI|9A95|123|uid4|u4|100004|2330|B|600|1000",
+			"Ord|O4|TwfOrd|1704157203.000002|R4|A0004|101|7|1000|",
+		]);
+		let rejected = rejected_orders(&parser.ord_rec);
+		let users: Vec<&str> = rejected.iter().map(|rej| rej.user.as_str()).collect();
+		assert_eq!(users, ["u1", "u2", "u3"]);
+		assert_eq!(rejected[0].ord.get_line_no(), 6);
+
+		let groups: Vec<(&str, Vec<&str>)> = group_by_reason(&rejected).into_iter()
+			.map(|(reason, list)| (reason, list.iter().map(|rej| rej.req_key.as_str()).collect()))
+			.collect();
+		assert_eq!(groups, [
+			("Insufficient margin", vec!["R1", "R2"]),
+			(NO_REASON, vec!["R3"]),
+		]);
+	}
+
+	#[test]
+	fn rejects_each_failed_request_of_an_order() {
+		// field 4 的名稱不一定是 ReqKey
+		let parser = parse(&[
+			"Ord|-|FrfOrd|Time|OrgReqKey|OrdNo|OrderSt|ReqStep|LeavesQty|ErrMsg",
+			"Req|R1|TwfNew|1704157200.000001|1|SorAPI|9A95|123|uid1|u1|100001|2330|B|600|1000",
+			"Ord|O1|FrfOrd|1704157200.000002|R1|A0001|101|7|1000|",
+			"Req|R2|TwfChg|1704157201.000001|2|SorAPI|u1|100002|500",
+			"Ord|O1|FrfOrd|1704157201.000002|R2|A0001|99|99|1000|Qty exceeds leaves",
+			"Req|R3|TwfChg|1704157202.000001|4|SorAPI|u1|100003|0",
+			"Ord|O1|FrfOrd|1704157202.000002|R3|A0001|99|99|1000|Order locked",
+			"Ord|O1|FrfOrd|1704157202.000003|R3|A0001|99|99|1000|Order locked",
+		]);
+		let rejected = rejected_orders(&parser.ord_rec);
+		let found: Vec<(&str, &str, &str)> = rejected.iter().map(|rej| (rej.req_key.as_str(), rej.kind.as_str(), rej.reason.as_str())).collect();
+		assert_eq!(found, [("R2", "2", "Qty exceeds leaves"), ("R3", "4", "Order locked")]);
+	}
+}
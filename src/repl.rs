@@ -0,0 +1,236 @@
+use std::collections::LinkedList;
+use std::sync::Arc;
+
+use rustyline::completion::{Completer, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
+
+use crate::check;
+use crate::fills;
+use crate::gaps::{self, TradingHours, DEFAULT_GAP_SECS};
+use crate::parser::{Parser, Rec, LOG_TABLE};
+use crate::positions::PositionReport;
+use crate::rejects;
+
+// 互動式查詢: 只解析一次 SorReqOrd.log，之後以指令反覆查詢
+
+const COMMANDS: [&str; 18] = ["find", "text", "regex", "show", "stat", "flow", "fills", "positions", "check", "gaps", "rejects", "tables", "fields", "tags", "export", "info", "help", "quit"];
+
+const HELP: &str = "\
+find <conditions>     search, ex: find TwfNew:User:u1,TwfNew:Side:B|TwfChg:Side:B
+text <words>          search the attached log lines for the words (case-insensitive)
+regex <pattern>       search the attached log lines with a regular expression
+show <OrdKey>         show an order's Req/Ord records by Ord key
+stat <Table:Field>    statistic a field's values, ex: stat TwfNew:User
+flow                  requests per second
+fills [problems]      filled quantity and average price per order, problems: flagged orders only
+positions             bought/sold quantity, net position and turnover per account and symbol
+check                 data-quality anomalies by category, with their line numbers
+gaps [seconds]        gaps between records in trading hours (default 5s), with the orders in flight
+rejects               rejected requests grouped by reason
+tables                list table names
+fields <Table>        list field names of a table
+tags                  list the tags of the ':' log lines, usable as log:Tag:Value in find
+export <file>         save the last find/text/regex result to a file
+info                  summary of the parsed log
+help                  show this help
+quit                  leave";
+
+/// 以 tables 的表名、欄位名做 tab 補完
+struct ReplHelper {
+	tables: Vec<(String, Vec<String>)>, // 表名-欄位名
+}
+
+impl ReplHelper {
+	fn new(parser: &Parser) -> ReplHelper {
+		let mut tables: Vec<(String, Vec<String>)> = parser.ord_rec.tables.iter()
+			.map(|(name, tab)| (name.clone(), tab.recs.iter().skip(3).cloned().collect()))
+			.collect();
+		tables.push((LOG_TABLE.to_string(), parser.ord_rec.log_tags().into_keys().collect()));
+		tables.sort();
+		ReplHelper { tables }
+	}
+	fn candidates<'a, I: Iterator<Item = &'a String>>(names: I, prefix: &str, suffix: &str) -> Vec<Pair> {
+		names.filter(|n| n.starts_with(prefix))
+			.map(|n| Pair { display: n.clone(), replacement: format!("{}{}", n, suffix) })
+			.collect()
+	}
+}
+
+impl Completer for ReplHelper {
+	type Candidate = Pair;
+	fn complete(&self, line: &str, pos: usize, _ctx: &Context<'_>) -> rustyline::Result<(usize, Vec<Pair>)> {
+		let head = &line[..pos];
+		// 第一個字補完指令
+		let cmd_end = match head.find(' ') {
+			Some(idx) => idx,
+			None => {
+				let cmds = COMMANDS.iter().filter(|c| c.starts_with(head)).map(|c| Pair { display: c.to_string(), replacement: format!("{} ", c) });
+				return Ok((0, cmds.collect()));
+			}
+		};
+		let cmd = &head[..cmd_end];
+		if !["find", "stat", "fields"].contains(&cmd) {
+			return Ok((pos, Vec::new()));
+		}
+		// 條件以 ' ' ',' '|' 分隔，補完目前這個條件的表名或欄位名
+		let start = head.rfind([' ', ',', '|']).map(|i| i + 1).unwrap_or(0);
+		let word = &head[start..];
+		let toks: Vec<&str> = word.split(':').collect();
+		let suffix = if cmd == "fields" { "" } else { ":" };
+		match toks.len() {
+			1 => Ok((start, Self::candidates(self.tables.iter().map(|t| &t.0), word, suffix))),
+			2 if cmd != "fields" => {
+				let fields = self.tables.iter().find(|t| t.0 == toks[0]).map(|t| &t.1);
+				match fields {
+					Some(fields) => {
+						let field_suffix = if cmd == "find" { ":" } else { "" };
+						Ok((start + toks[0].len() + 1, Self::candidates(fields.iter(), toks[1], field_suffix)))
+					},
+					None => Ok((pos, Vec::new())),
+				}
+			},
+			_ => Ok((pos, Vec::new())),
+		}
+	}
+}
+
+impl Hinter for ReplHelper {
+	type Hint = String;
+}
+impl Highlighter for ReplHelper {}
+impl Validator for ReplHelper {}
+impl Helper for ReplHelper {}
+
+/// 歷史記錄檔放在 HOME 下，沒有 HOME 時放在目前目錄
+fn history_path() -> String {
+	match std::env::var("HOME") {
+		Ok(home) => format!("{}/.sor_logparser_history", home),
+		Err(_) => ".sor_logparser_history".to_string(),
+	}
+}
+
+/// 進入互動模式，直到輸入 quit 或 Ctrl-D
+pub fn run(mut parser: Parser) {
+	let mut rl = match Editor::<ReplHelper, DefaultHistory>::new() {
+		Ok(rl) => rl,
+		Err(e) => {
+			println!("cannot start interactive shell: {}", e);
+			return;
+		}
+	};
+	rl.set_helper(Some(ReplHelper::new(&parser)));
+	let history = history_path();
+	let _ = rl.load_history(&history);
+	println!("{}\ntype help for commands", parser);
+
+	let mut last_result: Option<LinkedList<LinkedList<Arc<Rec>>>> = None;
+	loop {
+		let line = match rl.readline("sor> ") {
+			Ok(line) => line,
+			Err(ReadlineError::Interrupted) => continue,
+			Err(_) => break,
+		};
+		let line = line.trim();
+		if line.is_empty() {
+			continue;
+		}
+		let _ = rl.add_history_entry(line);
+		let (cmd, arg) = match line.split_once(' ') {
+			Some((cmd, arg)) => (cmd, arg.trim()),
+			None => (line, ""),
+		};
+		if cmd == "quit" || cmd == "exit" {
+			break;
+		}
+		// 指令的錯誤只結束該指令，不離開互動模式
+		if let Err(e) = run_command(&mut parser, &mut last_result, cmd, arg) {
+			println!("{}", e);
+		}
+	}
+	let _ = rl.save_history(&history);
+}
+
+/// 執行一個指令，find、text、regex 的結果存到 last_result 供 export 使用
+/// 指令的參數或條件錯誤時回傳說明
+fn run_command(parser: &mut Parser, last_result: &mut Option<LinkedList<LinkedList<Arc<Rec>>>>, cmd: &str, arg: &str) -> Result<(), String> {
+	match cmd {
+		"find" | "text" | "regex" => {
+			let ret = match cmd {
+				"find" => parser.search(arg, &true, false)?,
+				"text" => parser.search_log_text(arg),
+				_ => parser.search_log_regex(arg).map_err(|e| format!("invalid regex {}: {}", arg, e))?,
+			};
+			if ret.is_empty() {
+				println!("not found any matches");
+			} else {
+				println!("{} occurence found.", ret.len());
+				for list in &ret {
+					parser.ord_rec.print_ord_list(list);
+				}
+				*last_result = Some(ret);
+			}
+		},
+		"show" => {
+			let list = parser.ord_rec.get_target_ordlist(arg);
+			if list.is_empty() {
+				println!("{} not found", arg);
+			} else {
+				parser.ord_rec.print_ord_list(&list);
+			}
+		},
+		"stat" => {
+			match arg.split_once(':') {
+				Some((table, field)) => println!("{}", parser.statistic_field(table, field)),
+				None => println!("please correct format.  eg.: stat TwfNew:User"),
+			}
+		},
+		"flow" => print!("{}", parser.req_flow_statistic()),
+		"fills" => print!("{}", fills::format_report(&parser.ord_rec, arg == "problems")),
+		"check" => print!("{}", check::format_report(&parser.ord_rec)),
+		"rejects" => print!("{}", rejects::format_report(&parser.ord_rec)),
+		"gaps" => {
+			let min_secs = if arg.is_empty() { DEFAULT_GAP_SECS } else { arg.parse::<f64>().map_err(|e| format!("invalid seconds {}: {}", arg, e))? };
+			if let Ok(hours) = TradingHours::parse(gaps::DEFAULT_TRADING_HOURS) {
+				print!("{}", gaps::format_report(&gaps::find_gaps(&parser.ord_rec, min_secs, &hours), min_secs));
+			}
+		},
+		"positions" => {
+			let mut report = PositionReport::default();
+			report.add_log(&parser.ord_rec);
+			print!("{}", report.format());
+		},
+		"tables" => {
+			let mut names: Vec<&String> = parser.ord_rec.tables.keys().collect();
+			names.sort();
+			for name in names {
+				println!("{}", name);
+			}
+		},
+		"fields" => {
+			match parser.ord_rec.tables.get(arg) {
+				Some(tab) => println!("{}", tab.recs.iter().skip(3).cloned().collect::<Vec<_>>().join(" ")),
+				None => println!("{} doesn't exist", arg),
+			}
+		},
+		"export" => {
+			match (&*last_result, arg.is_empty()) {
+				(None, _) => println!("no result to export, run find, text or regex first"),
+				(_, true) => println!("please specify the file name"),
+				(Some(ret), false) => {
+					parser.save_to_file(ret, arg);
+					println!("{} orders saved to {}", ret.len(), arg);
+				},
+			}
+		},
+		"tags" => print!("{}", parser.log_tags_statistic()),
+		"info" => print!("{}", parser.get_info()),
+		"help" => println!("{}", HELP),
+		_ => return Err(format!("unknown command {}, type help for commands", cmd)),
+	}
+	Ok(())
+}
@@ -0,0 +1,401 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap, LinkedList};
+use std::io::{Error, Result};
+use std::path::Path;
+use std::sync::Arc;
+
+use chrono::{Local, TimeZone};
+use serde_json::{json, Value};
+use tiny_http::{Header, Method, Response, Server};
+
+use crate::check;
+use crate::fileread::read_log_file;
+use crate::fills;
+use crate::gaps::{self, TradingHours};
+use crate::parser::{Parser, Rec};
+use crate::pki::PkiLayout;
+use crate::positions::PositionReport;
+use crate::rejects;
+
+// HTTP JSON API: 只解析一次 log，將結果留在記憶體中，供 dashboard 以 HTTP 查詢
+
+const ENDPOINTS: [(&str, &str); 14] = [
+	("/", "this list and the parsed sources"),
+	("/search?q=<conditions>", "orders matching the conditions (same syntax as -f)"),
+	("/text?q=<text> or /text?re=<regex>", "orders whose attached log lines contain the text or match the regex"),
+	("/order?key=<OrdKey or ReqKey>", "an order's Req/Ord records"),
+	("/tables", "table names with their type and field names"),
+	("/stat?table=<Table>&field=<Field>", "distinct values of a field (same as -t)"),
+	("/logtags", "tags of the ':' log lines with the number of records having each"),
+	("/flow", "requests per second (same as -w)"),
+	("/fills[?problems=1]", "filled quantity and average price per order, with the problems found (same as --fills)"),
+	("/check", "data-quality anomalies with their category, line number, table and key (same as --check)"),
+	("/gaps[?secs=<seconds>&hours=<HH:MM-HH:MM,...>]", "gaps between records in trading hours, with the bursts after them and the orders in flight (same as --gaps)"),
+	("/rejects", "rejected requests with their user, kind and reject text, grouped by reason (same as --rejects)"),
+	("/positions", "bought/sold quantity, net position, turnover and orders per account and symbol, by day and in total (same as --positions)"),
+	("/pki?date=<YYYYMMDD>[&q=<conditions>]", "PKI lines of the requests on that date"),
+];
+
+/// 一個已解析的 SorReqOrd.log
+struct Source {
+	name  : String, // 日期目錄名，單一檔案時為檔案路徑
+	path  : String,
+	parser: Parser,
+}
+
+/// 查詢的結果: HTTP 狀態碼及 JSON 內容
+type Reply = (u16, Value);
+
+fn error_reply(status: u16, message: &str) -> Reply {
+	(status, json!({ "error": message }))
+}
+
+/// 解析一個 log 檔，無法開啟時回傳 None
+fn load_source(path: &str, name: String, encoding: &str, layout: &PkiLayout) -> Option<Source> {
+	println!("Processing: {}", path);
+	let mut parser = Parser::new();
+	parser.set_pki_layout(layout.clone());
+	match read_log_file(Path::new(path), &mut parser, encoding) {
+		Ok(()) => Some(Source { name, path: path.to_string(), parser }),
+		Err(e) => {
+			println!("error opening {}: {}", path, e);
+			None
+		}
+	}
+}
+
+/// 解析指定的檔案，未指定時解析 scan_dir 下各日期目錄的 SorReqOrd.log
+fn load_sources(filepath: Option<&str>, scan_dir: &str, encoding: &str, layout: &PkiLayout) -> Vec<Source> {
+	if let Some(path) = filepath {
+		return load_source(path, path.to_string(), encoding, layout).into_iter().collect();
+	}
+	let date_dirs = match crate::find_date_directories(scan_dir) {
+		Ok(dirs) => dirs,
+		Err(e) => {
+			println!("Error reading directory {}: {}", scan_dir, e);
+			return Vec::new();
+		}
+	};
+	date_dirs.iter()
+		.filter_map(|dir| {
+			let log_path = Path::new(dir).join("SorReqOrd.log");
+			if !log_path.exists() {
+				return None;
+			}
+			let name = Path::new(dir).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+			load_source(&log_path.to_string_lossy(), name, encoding, layout)
+		})
+		.collect()
+}
+
+/// 委託的 JSON 加上所屬的來源
+fn order_json(source: &Source, list: &LinkedList<Arc<Rec>>) -> Value {
+	let mut order = source.parser.ord_rec.ord_list_to_json(list);
+	order["source"] = json!(source.name);
+	order
+}
+
+/// 列出 API 及已解析的來源
+fn index(sources: &[Source]) -> Reply {
+	let endpoints: Vec<Value> = ENDPOINTS.iter().map(|(path, desc)| json!({ "path": path, "description": desc })).collect();
+	let sources: Vec<Value> = sources.iter().map(|src| {
+		let counts = src.parser.get_counts();
+		json!({
+			"name"   : src.name,
+			"path"   : src.path,
+			"tables" : counts.tables,
+			"reqs"   : counts.reqs,
+			"ords"   : counts.ords,
+			"deals"  : counts.deals,
+			"invalid": counts.invalid,
+		})
+	}).collect();
+	(200, json!({ "endpoints": endpoints, "sources": sources }))
+}
+
+fn search(sources: &[&mut Source], conditions: &str) -> Reply {
+	if conditions.is_empty() {
+		return error_reply(400, "please specify q=TableName:FieldName:Value");
+	}
+	let mut orders = Vec::<Value>::new();
+	for src in sources {
+		match src.parser.search(conditions, &true, true) {
+			Ok(ret) => orders.extend(ret.iter().map(|list| order_json(src, list))),
+			Err(e) => return error_reply(400, &e),
+		}
+	}
+	(200, json!({ "count": orders.len(), "orders": orders }))
+}
+
+/// 在附加的 log 中全文 (q) 或以正規表示式 (re) 搜尋
+fn text(sources: &[&mut Source], text: &str, re: &str) -> Reply {
+	if text.is_empty() && re.is_empty() {
+		return error_reply(400, "please specify q=<text> or re=<regex>");
+	}
+	let mut orders = Vec::<Value>::new();
+	for src in sources {
+		let found = if re.is_empty() {
+			Ok(src.parser.search_log_text(text))
+		} else {
+			src.parser.search_log_regex(re)
+		};
+		match found {
+			Ok(ret) => orders.extend(ret.iter().map(|list| order_json(src, list))),
+			Err(e) => return error_reply(400, &format!("invalid regex {}: {}", re, e)),
+		}
+	}
+	(200, json!({ "count": orders.len(), "orders": orders }))
+}
+
+/// 以 Ord key 或 Req key 取得委託，Req 尚未有 Ord 時只回傳該 Req
+fn order(sources: &[&mut Source], key: &str) -> Reply {
+	let mut orders = Vec::<Value>::new();
+	for src in sources {
+		let ord_rec = &src.parser.ord_rec;
+		let list = match ord_rec.get_ord_key(key) {
+			Some(ord_key) => ord_rec.get_target_ordlist(ord_key),
+			None if ord_rec.ords.contains_key(key) => ord_rec.get_target_ordlist(key),
+			None => ord_rec.reqs.get(key).cloned().into_iter().collect(),
+		};
+		if !list.is_empty() {
+			orders.push(order_json(src, &list));
+		}
+	}
+	if orders.is_empty() {
+		return error_reply(404, &format!("{} not found", key));
+	}
+	(200, json!({ "orders": orders }))
+}
+
+/// 各表格的種類 (Req/Ord) 及欄位名，各來源的同名表格只列一次
+fn tables(sources: &[&mut Source]) -> Reply {
+	let mut tables = BTreeMap::<String, Value>::new();
+	for src in sources {
+		for (name, tab) in &src.parser.ord_rec.tables {
+			tables.entry(name.clone()).or_insert_with(|| json!({
+				"type"  : tab.recs.first(),
+				"fields": tab.recs.iter().skip(3).collect::<Vec<_>>(),
+			}));
+		}
+	}
+	(200, json!(tables))
+}
+
+/// 欄位在各來源出現過的值，所有來源都沒有該表格/欄位時回傳 404
+fn stat(sources: &[&mut Source], table: &str, field: &str) -> Reply {
+	if table.is_empty() || field.is_empty() {
+		return error_reply(400, "please specify table=<Table>&field=<Field>");
+	}
+	let mut values = BTreeSet::<String>::new();
+	let mut error = None;
+	let mut found = false;
+	for src in sources {
+		match src.parser.ord_rec.field_values(table, field) {
+			Ok(set) => {
+				found = true;
+				values.extend(set);
+			},
+			Err(e) => error = Some(e),
+		}
+	}
+	if !found {
+		return error_reply(404, &error.unwrap_or_else(|| format!("there is no {} table", table)));
+	}
+	(200, json!({ "table": table, "field": field, "count": values.len(), "values": values }))
+}
+
+/// 各來源的 log 欄位名及含有該欄位的記錄數 (合計)
+fn log_tags(sources: &[&mut Source]) -> Reply {
+	let mut tags = BTreeMap::<String, usize>::new();
+	for src in sources {
+		for (tag, count) in src.parser.ord_rec.log_tags() {
+			*tags.entry(tag).or_default() += count;
+		}
+	}
+	(200, json!(tags))
+}
+
+fn flow(sources: &[&mut Source]) -> Reply {
+	let mut seconds: Vec<(i64, i32)> = sources.iter().flat_map(|src| src.parser.req_flow()).collect();
+	seconds.sort_by_key(|s| s.0);
+	let peak = seconds.iter().max_by_key(|s| s.1).map(|s| json!({ "secs": s.0, "count": s.1 }));
+	let total: i64 = seconds.iter().map(|s| s.1 as i64).sum();
+	let seconds: Vec<Value> = seconds.iter().map(|(secs, count)| {
+		let time = Local.timestamp_opt(*secs, 0).single().map(|t| t.format("%Y/%m/%d %H:%M:%S").to_string()).unwrap_or_default();
+		json!({ "secs": secs, "time": time, "count": count })
+	}).collect();
+	(200, json!({ "total": total, "peak": peak, "seconds": seconds }))
+}
+
+/// 各委託的成交統計，problems 時只列出有問題的委託
+fn fills(sources: &[&mut Source], only_problems: bool) -> Reply {
+	let mut orders = Vec::<Value>::new();
+	let mut unlinked = 0;
+	for src in sources {
+		let ord_rec = &src.parser.ord_rec;
+		unlinked += fills::unlinked_deals(ord_rec);
+		orders.extend(fills::fill_report(ord_rec).iter()
+			.filter(|fill| !only_problems || !fill.problems.is_empty())
+			.map(|fill| json!({
+				"source"   : src.name,
+				"ord_key"  : fill.ord_key,
+				"rid"      : fill.rid,
+				"ordno"    : fill.ordno,
+				"table"    : fill.table,
+				"order_qty": fill.order_qty,
+				"filled"   : fill.filled,
+				"avg_price": fill.avg_price,
+				"deals"    : fill.deals,
+				"order_st" : fill.order_st,
+				"problems" : fill.problems.iter().map(|p| p.to_string()).collect::<Vec<_>>(),
+			})));
+	}
+	(200, json!({ "count": orders.len(), "unlinked_deals": unlinked, "orders": orders }))
+}
+
+/// 資料品質檢查，各類別的筆數及異常的記錄
+fn check(sources: &[&mut Source]) -> Reply {
+	let mut counts = BTreeMap::<String, usize>::new();
+	let mut anomalies = Vec::<Value>::new();
+	for src in sources {
+		for anomaly in check::check(&src.parser.ord_rec) {
+			*counts.entry(anomaly.kind.to_string()).or_default() += 1;
+			anomalies.push(json!({
+				"source" : src.name,
+				"kind"   : anomaly.kind.to_string(),
+				"line_no": anomaly.line_no,
+				"table"  : anomaly.table,
+				"key"    : anomaly.key,
+				"detail" : anomaly.detail,
+			}));
+		}
+	}
+	(200, json!({ "count": anomalies.len(), "kinds": counts, "anomalies": anomalies }))
+}
+
+/// 記錄中斷檢查，未指定時使用預設的門檻及交易時段
+fn gaps(sources: &[&mut Source], secs: &str, hours: &str) -> Reply {
+	let min_secs = if secs.is_empty() { gaps::DEFAULT_GAP_SECS } else {
+		match secs.parse::<f64>() {
+			Ok(secs) => secs,
+			Err(_) => return error_reply(400, &format!("invalid secs {}", secs)),
+		}
+	};
+	let hours = match TradingHours::parse(if hours.is_empty() { gaps::DEFAULT_TRADING_HOURS } else { hours }) {
+		Ok(hours) => hours,
+		Err(e) => return error_reply(400, &e),
+	};
+	let reports: Vec<Value> = sources.iter().map(|src| {
+		let mut report = gaps::report_json(&gaps::find_gaps(&src.parser.ord_rec, min_secs, &hours));
+		report["source"] = json!(src.name);
+		report
+	}).collect();
+	(200, json!({ "secs": min_secs, "sources": reports }))
+}
+
+/// 失敗委託，依原因分組
+fn rejects(sources: &[&mut Source]) -> Reply {
+	let reports: Vec<Value> = sources.iter().map(|src| {
+		let mut report = rejects::report_json(&rejects::rejected_orders(&src.parser.ord_rec));
+		report["source"] = json!(src.name);
+		report
+	}).collect();
+	(200, json!({ "sources": reports }))
+}
+
+/// 各帳戶、商品的部位，依日期及合計
+fn positions(sources: &[&mut Source]) -> Reply {
+	let mut report = PositionReport::default();
+	for src in sources {
+		report.add_log(&src.parser.ord_rec);
+	}
+	(200, report.to_json())
+}
+
+/// date 當天的 Req 產生的PKI記錄 (依時間排序) 及被剔除、標示的記錄
+fn pki(sources: &mut [&mut Source], date: &str, conditions: &str) -> Reply {
+	if date.len() != 8 || !date.chars().all(|c| c.is_ascii_digit()) {
+		return error_reply(400, "please specify date=YYYYMMDD");
+	}
+	let mut lines = Vec::<(f64, String)>::new();
+	let mut rejects = Vec::<String>::new();
+	for src in sources.iter_mut() {
+		let parser = &mut src.parser;
+		let src_lines = if conditions.is_empty() {
+			parser.get_pki_records()
+		} else {
+			if let Err(e) = parser.find_by_conditions(conditions, "", &true, true, true) {
+				return error_reply(400, &e);
+			}
+			parser.take_pki_lines()
+		};
+		let src_rejects = parser.take_pki_rejects(&src.path);
+		let reqs = &parser.ord_rec.reqs;
+		let on_date = |req_key: &str| reqs.get(req_key).is_some_and(|req| req.get_date() == date);
+		lines.extend(src_lines.into_iter()
+			.filter(|l| on_date(&l.req_key))
+			.map(|l| (reqs[&l.req_key].get_secs().unwrap_or(0.0), l.line)));
+		rejects.extend(src_rejects.iter().filter(|r| on_date(&r.req_key)).map(|r| r.to_string()));
+	}
+	lines.sort_by(|a, b| a.0.total_cmp(&b.0));
+	let lines: Vec<String> = lines.into_iter().map(|l| l.1).collect();
+	(200, json!({ "date": date, "count": lines.len(), "lines": lines, "rejects": rejects }))
+}
+
+/// 處理一個 GET 請求，source=<name> 時只查詢該來源
+fn handle(sources: &mut [Source], url: &str) -> Reply {
+	let (path, query) = url.split_once('?').unwrap_or((url, ""));
+	let params: HashMap<String, String> = form_urlencoded::parse(query.as_bytes()).into_owned().collect();
+	let param = |name: &str| params.get(name).map(|v| v.trim()).unwrap_or("");
+
+	if path == "/" {
+		return index(sources);
+	}
+	let source = param("source");
+	let mut selected: Vec<&mut Source> = sources.iter_mut().filter(|src| source.is_empty() || src.name == source).collect();
+	if selected.is_empty() {
+		return error_reply(404, &format!("source {} not found", source));
+	}
+	match path {
+		"/search" => search(&selected, param("q")),
+		"/text"   => text(&selected, param("q"), param("re")),
+		"/order"  => order(&selected, param("key")),
+		"/tables" => tables(&selected),
+		"/stat"   => stat(&selected, param("table"), param("field")),
+		"/logtags" => log_tags(&selected),
+		"/flow"   => flow(&selected),
+		"/fills"  => fills(&selected, !param("problems").is_empty() && param("problems") != "0"),
+		"/positions" => positions(&selected),
+		"/check"  => check(&selected),
+		"/rejects" => rejects(&selected),
+		"/gaps"   => gaps(&selected, param("secs"), param("hours")),
+		"/pki"    => pki(&mut selected, param("date"), param("q")),
+		_ => error_reply(404, &format!("unknown path {}, see /", path)),
+	}
+}
+
+/// 解析 log 後在 listen 位址提供 HTTP JSON API，直到程式結束
+pub fn run(filepath: Option<&str>, scan_dir: &str, encoding: &str, layout: &PkiLayout, listen: &str) -> Result<()> {
+	let mut sources = load_sources(filepath, scan_dir, encoding, layout);
+	if sources.is_empty() {
+		println!("No SorReqOrd.log to serve");
+		return Ok(());
+	}
+	let server = Server::http(listen).map_err(|e| Error::other(format!("cannot listen on {}: {}", listen, e)))?;
+	println!("{} logs parsed, serving on http://{}/", sources.len(), listen);
+	let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json; charset=utf-8"[..]).unwrap();
+
+	for request in server.incoming_requests() {
+		let (status, body) = if *request.method() == Method::Get {
+			handle(&mut sources, request.url())
+		} else {
+			error_reply(405, "only GET is supported")
+		};
+		let response = Response::from_string(body.to_string())
+			.with_status_code(status)
+			.with_header(content_type.clone());
+		if let Err(e) = request.respond(response) {
+			println!("error responding: {}", e);
+		}
+	}
+	Ok(())
+}
@@ -0,0 +1,107 @@
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Result};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::UNIX_EPOCH;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::parser::OrderRec;
+
+// 解析結果 (OrderRec) 的快照: 解析完存成二進位檔，下次讀同一個 log 時直接載入
+// 以 log 的路徑、大小、修改時間及編碼判斷快照是否仍有效，log 有變動時重新解析並覆蓋快照
+
+/// 快照格式的版本，OrderRec 的結構改變時要加一，使舊的快照失效
+const SNAPSHOT_VERSION: u32 = 2;
+
+/// 是否使用快照 (--no-cache 時關閉)
+static ENABLED: AtomicBool = AtomicBool::new(true);
+
+pub fn set_enabled(enabled: bool) {
+	ENABLED.store(enabled, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+	ENABLED.load(Ordering::Relaxed)
+}
+
+/// 快照檔的表頭，與目前的 log 相同時快照才有效
+#[derive(Serialize, Deserialize, PartialEq)]
+struct SnapshotKey {
+	version : u32,
+	path    : String,
+	size    : u64,
+	mtime   : u128, // 修改時間 (UNIX epoch 起的 ns)
+	encoding: String,
+}
+
+impl SnapshotKey {
+	fn new(log_path: &Path, encoding: &str) -> Result<SnapshotKey> {
+		let metadata = fs::metadata(log_path)?;
+		let mtime = metadata.modified()?.duration_since(UNIX_EPOCH).map(|d| d.as_nanos()).unwrap_or(0);
+		Ok(SnapshotKey {
+			version : SNAPSHOT_VERSION,
+			path    : fs::canonicalize(log_path)?.to_string_lossy().to_string(),
+			size    : metadata.len(),
+			mtime,
+			encoding: encoding.to_string(),
+		})
+	}
+}
+
+/// 快照放在 HOME 下的 .cache/sor_logparser，沒有 HOME 時放在目前目錄的 .sor_logparser_cache
+fn cache_dir() -> PathBuf {
+	match std::env::var("HOME") {
+		Ok(home) => Path::new(&home).join(".cache").join("sor_logparser"),
+		Err(_) => PathBuf::from(".sor_logparser_cache"),
+	}
+}
+
+/// log 對應的快照檔: 以絕對路徑的 SHA-256 命名，同一個 log 只會有一個快照
+fn snapshot_path(key: &SnapshotKey) -> PathBuf {
+	let hash = format!("{:x}", Sha256::digest(key.path.as_bytes()));
+	cache_dir().join(format!("{}.bin", &hash[..32]))
+}
+
+/// 載入 log 的快照，沒有快照或已失效時回傳 None
+pub fn load(log_path: &Path, encoding: &str) -> Option<OrderRec> {
+	if !is_enabled() {
+		return None;
+	}
+	let key = SnapshotKey::new(log_path, encoding).ok()?;
+	let mut reader = BufReader::new(File::open(snapshot_path(&key)).ok()?);
+	let saved: SnapshotKey = bincode::deserialize_from(&mut reader).ok()?;
+	if saved != key {
+		return None;
+	}
+	match bincode::deserialize_from(&mut reader) {
+		Ok(ord_rec) => Some(ord_rec),
+		Err(e) => {
+			println!("cannot load snapshot of {}: {}", log_path.display(), e);
+			None
+		}
+	}
+}
+
+/// 存下 log 的解析結果，先寫到暫存檔再改名，避免留下不完整的快照
+pub fn save(log_path: &Path, encoding: &str, ord_rec: &OrderRec) {
+	if !is_enabled() {
+		return;
+	}
+	if let Err(e) = try_save(log_path, encoding, ord_rec) {
+		println!("cannot save snapshot of {}: {}", log_path.display(), e);
+	}
+}
+
+fn try_save(log_path: &Path, encoding: &str, ord_rec: &OrderRec) -> Result<()> {
+	let key = SnapshotKey::new(log_path, encoding)?;
+	let path = snapshot_path(&key);
+	fs::create_dir_all(cache_dir())?;
+	let tmp_path = path.with_extension("tmp");
+	let mut writer = BufWriter::new(File::create(&tmp_path)?);
+	bincode::serialize_into(&mut writer, &key).map_err(std::io::Error::other)?;
+	bincode::serialize_into(&mut writer, ord_rec).map_err(std::io::Error::other)?;
+	writer.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+	fs::rename(tmp_path, path)
+}
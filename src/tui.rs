@@ -0,0 +1,218 @@
+use std::collections::{HashSet, LinkedList};
+use std::io::Result;
+use std::sync::Arc;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Modifier, Style};
+use ratatui::widgets::{Block, Borders, Paragraph, Row, Table, TableState, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+use crate::parser::{Parser, Rec};
+
+// 終端機介面的委託瀏覽器: 列出所有委託，以條件語法過濾，並可展開單筆委託的 Req/Ord 記錄
+
+/// 清單中的一筆委託，沒有 Ord 的委託以 Req key 表示
+struct OrderRow {
+	is_req: bool,
+	key   : String,
+	time  : String,
+	rid   : String,
+	ordno : String,
+	status: String,
+}
+
+struct App<'a> {
+	parser : &'a Parser,
+	all    : Vec<OrderRow>,
+	orders : Vec<usize>,          // 目前顯示的委託 (all 的 index)
+	state  : TableState,
+	filter : String,
+	editing: bool,                // 是否正在輸入過濾條件
+	detail : Option<(String, u16)>, // 展開的內容及捲動位置
+	message: String,
+}
+
+impl<'a> App<'a> {
+	fn new(parser: &'a Parser) -> App<'a> {
+		let ord_rec = &parser.ord_rec;
+		let req_only = ord_rec.reqs.keys().filter(|key| ord_rec.get_ord_key(key).is_none()).map(|key| (true, key));
+		let mut all: Vec<OrderRow> = ord_rec.ords.keys().map(|key| (false, key)).chain(req_only).map(|(is_req, key)| {
+			let list = order_list(parser, is_req, key);
+			let info = ord_rec.get_ord_summary(&list);
+			OrderRow {
+				is_req,
+				key   : key.clone(),
+				time  : list.front().map(|r| r.get_timestamp()).unwrap_or_default(),
+				rid   : info.rid().to_string(),
+				ordno : info.ordno().to_string(),
+				status: info.status().to_string(),
+			}
+		}).collect();
+		all.sort_by(|a, b| a.time.cmp(&b.time));
+		let orders = (0..all.len()).collect();
+		let message = format!("{} orders", all.len());
+		App {
+			parser,
+			all,
+			orders,
+			state: TableState::default().with_selected(Some(0)),
+			filter: String::new(),
+			editing: false,
+			detail: None,
+			message,
+		}
+	}
+
+	/// 以條件語法過濾委託，空字串顯示全部
+	fn apply_filter(&mut self) {
+		if self.filter.trim().is_empty() {
+			self.orders = (0..self.all.len()).collect();
+			self.message = format!("{} orders", self.all.len());
+		} else {
+			match self.parser.search(&self.filter, &true, true) {
+				Ok(ret) if !ret.is_empty() => {
+					// 有 Ord 的委託以 Ord key 比對，只有 Req 的以 Req key 比對
+					let keys: HashSet<(bool, &str)> = ret.iter()
+						.filter_map(|list| list.iter().find(|r| !r.is_req()).or(list.front()))
+						.map(|r| (r.is_req(), r.get_key()))
+						.collect();
+					self.orders = (0..self.all.len())
+						.filter(|i| keys.contains(&(self.all[*i].is_req, self.all[*i].key.as_str())))
+						.collect();
+					self.message = format!("{} orders match {}", self.orders.len(), self.filter);
+				},
+				Ok(_) => {
+					self.orders.clear();
+					self.message = format!("not found any matches of {}", self.filter);
+				},
+				Err(e) => {
+					self.orders.clear();
+					self.message = e;
+				}
+			}
+		}
+		self.state.select(if self.orders.is_empty() { None } else { Some(0) });
+	}
+
+	/// 展開目前選擇的委託
+	fn open_detail(&mut self) {
+		if let Some(row) = self.state.selected().and_then(|i| self.orders.get(i)).map(|i| &self.all[*i]) {
+			let list = order_list(self.parser, row.is_req, &row.key);
+			let text = self.parser.ord_rec.ord_list_to_string(&list).replace('\x01', "|");
+			self.detail = Some((text, 0));
+		}
+	}
+
+	fn move_selection(&mut self, delta: i64) {
+		if self.orders.is_empty() {
+			return;
+		}
+		let cur = self.state.selected().unwrap_or(0) as i64;
+		let next = (cur + delta).clamp(0, self.orders.len() as i64 - 1);
+		self.state.select(Some(next as usize));
+	}
+
+	/// 處理按鍵，回傳 false 表示離開
+	fn on_key(&mut self, code: KeyCode) -> bool {
+		if self.editing {
+			match code {
+				KeyCode::Enter => { self.editing = false; self.apply_filter(); },
+				KeyCode::Esc => self.editing = false,
+				KeyCode::Backspace => { self.filter.pop(); },
+				KeyCode::Char(c) => self.filter.push(c),
+				_ => {},
+			}
+			return true;
+		}
+		if let Some((_, scroll)) = &mut self.detail {
+			match code {
+				KeyCode::Esc | KeyCode::Backspace | KeyCode::Left => self.detail = None,
+				KeyCode::Up | KeyCode::Char('k') => *scroll = scroll.saturating_sub(1),
+				KeyCode::Down | KeyCode::Char('j') => *scroll = scroll.saturating_add(1),
+				KeyCode::PageUp => *scroll = scroll.saturating_sub(20),
+				KeyCode::PageDown => *scroll = scroll.saturating_add(20),
+				KeyCode::Char('q') => return false,
+				_ => {},
+			}
+			return true;
+		}
+		match code {
+			KeyCode::Char('q') | KeyCode::Esc => return false,
+			KeyCode::Char('/') => self.editing = true,
+			KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+			KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+			KeyCode::PageUp => self.move_selection(-20),
+			KeyCode::PageDown => self.move_selection(20),
+			KeyCode::Home => self.move_selection(i64::MIN / 2),
+			KeyCode::End => self.move_selection(i64::MAX / 2),
+			KeyCode::Enter | KeyCode::Right => self.open_detail(),
+			_ => {},
+		}
+		true
+	}
+
+	fn draw(&mut self, frame: &mut Frame) {
+		let [filter_area, main_area, help_area] = Layout::vertical([
+			Constraint::Length(3), Constraint::Min(1), Constraint::Length(1),
+		]).areas(frame.area());
+
+		let filter_title = if self.editing { "Filter (Enter: apply, Esc: cancel)" } else { "Filter (/: edit)" };
+		let filter = Paragraph::new(self.filter.as_str())
+			.block(Block::default().borders(Borders::ALL).title(filter_title));
+		frame.render_widget(filter, filter_area);
+
+		if let Some((text, scroll)) = &self.detail {
+			let detail = Paragraph::new(text.as_str())
+				.block(Block::default().borders(Borders::ALL).title("Order (Esc: back)"))
+				.wrap(Wrap { trim: false })
+				.scroll((*scroll, 0));
+			frame.render_widget(detail, main_area);
+		} else {
+			let rows = self.orders.iter().map(|i| {
+				let o = &self.all[*i];
+				Row::new(vec![o.time.clone(), o.key.clone(), o.rid.clone(), o.ordno.clone(), o.status.clone()])
+			});
+			let widths = [Constraint::Length(27), Constraint::Length(16), Constraint::Length(12), Constraint::Length(10), Constraint::Min(20)];
+			let table = Table::new(rows, widths)
+				.header(Row::new(vec!["Time", "Key", "SorRID", "OrdNo", "Status"]).style(Style::default().add_modifier(Modifier::BOLD)))
+				.block(Block::default().borders(Borders::ALL).title(self.message.as_str()))
+				.row_highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+			frame.render_stateful_widget(table, main_area, &mut self.state);
+		}
+
+		let help = "q: quit  /: filter  Up/Down: move  Enter: open order  Esc: back";
+		frame.render_widget(Paragraph::new(help), help_area);
+	}
+}
+
+/// 委託的 Req/Ord 記錄，沒有 Ord 的委託只有該筆 Req
+fn order_list(parser: &Parser, is_req: bool, key: &str) -> LinkedList<Arc<Rec>> {
+	if is_req {
+		parser.ord_rec.reqs.get(key).map(|req| LinkedList::from([Arc::clone(req)])).unwrap_or_default()
+	} else {
+		parser.ord_rec.get_target_ordlist(key)
+	}
+}
+
+fn event_loop(terminal: &mut DefaultTerminal, app: &mut App) -> Result<()> {
+	loop {
+		terminal.draw(|frame| app.draw(frame))?;
+		if let Event::Key(key) = event::read()? {
+			if key.kind == KeyEventKind::Press && !app.on_key(key.code) {
+				return Ok(());
+			}
+		}
+	}
+}
+
+/// 進入終端機介面，直到按下 q
+pub fn run(parser: &mut Parser) -> Result<()> {
+	// 查詢時印出的訊息會破壞畫面
+	parser.ord_rec.quiet = true;
+	let mut app = App::new(parser);
+	let mut terminal = ratatui::init();
+	let result = event_loop(&mut terminal, &mut app);
+	ratatui::restore();
+	result
+}
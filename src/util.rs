@@ -0,0 +1,10 @@
+// 各模式共用的輔助函式
+
+/// CSV 欄位: 含逗號、引號或換行時加上引號
+pub fn csv_field(value: &str) -> String {
+	if value.contains([',', '"', '\n', '\r']) {
+		format!("\"{}\"", value.replace('"', "\"\""))
+	} else {
+		value.to_string()
+	}
+}